@@ -0,0 +1,327 @@
+//!
+//! Line Protocol Encoding Benchmark
+//!
+//! Compares streaming a 50k point Record straight into a buffer or the gzip encoder against the previous approach of
+//! encoding every measurement into its own String, joining them into one buffer and only then compressing a copy of
+//! it. That approach is copied below as it was, along with the parts of Record and Measurement it relied on, so changes
+//! to the crate's own encoder do not leak into the baseline. As it predates escaping and type suffixes, its output is
+//! not compared with the one of the crate.
+//!
+use influxc::Value;
+use influxc::Record;
+use influxc::Precision;
+
+use criterion::Criterion;
+use criterion::criterion_main;
+use criterion::criterion_group;
+
+use chrono::TimeZone;
+
+use flate2::Compression as FlateLevel;
+use flate2::GzBuilder   as FlateGzipBuilder;
+
+use reqwest::blocking::RequestBuilder as ReqwRequestBuilder;
+
+use std::io::Write;
+use std::io::BufWriter;
+
+use std::hint::black_box;
+
+
+type DateTime = chrono::DateTime<chrono::Utc>;
+
+const POINTS: i64 = 50_000;
+
+
+fn timestamp(idx: i64) -> DateTime
+{
+    chrono::Utc.timestamp_millis_opt(1_600_000_000_000 + idx).unwrap()
+}
+
+
+fn record() -> Record
+{
+    let mut rec = Record::new("org", "bucket")
+        .precision(Precision::Milliseconds);
+
+    for idx in 0..POINTS
+    {
+        rec.measurement("sensor data")
+            .tag("floor", "second")
+            .tag("exposure", "west, upper")
+            .tag("device", &format!("dev-{}", idx % 64))
+            .field("temp", 21.5 + idx as f64 / 1000.0)
+            .field("brightness", idx)
+            .field("label", format!("reading \"{}\"", idx))
+            .field("ok", true)
+            .timestamp(timestamp(idx));
+    }
+
+    rec
+}
+
+
+fn baseline_record() -> baseline::Record
+{
+    let mut rec = baseline::Record::new("org", "bucket")
+        .precision(Precision::Milliseconds);
+
+    for idx in 0..POINTS
+    {
+        rec.measurement("sensor data")
+            .tag("floor", "second")
+            .tag("exposure", "west, upper")
+            .tag("device", &format!("dev-{}", idx % 64))
+            .field("temp", 21.5 + idx as f64 / 1000.0)
+            .field("brightness", idx)
+            .field("label", format!("reading \"{}\"", idx))
+            .field("ok", true)
+            .timestamp(timestamp(idx));
+    }
+
+    rec
+}
+
+
+/// Record and Measurement as they were before streaming, down to the encoding of every line into its own String
+mod baseline
+{
+    use super::Value;
+    use super::Precision;
+    use super::DateTime;
+
+    use super::FlateLevel;
+    use super::FlateGzipBuilder;
+    use super::ReqwRequestBuilder;
+
+    use std::io::Write;
+
+    use std::collections::BTreeMap;
+
+    type InfluxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+
+    pub(crate) struct Measurement
+    {
+        pub(crate) name: String,
+
+        pub(crate) tags:   BTreeMap<String, String>,
+        pub(crate) fields: BTreeMap<String, Value>,
+
+        pub(crate) timestamp: DateTime,
+    }
+
+
+    impl Measurement
+    {
+        pub(crate) fn new(name: &str) -> Self
+        {
+            Self {
+                name: name.to_owned(),
+
+                tags:   BTreeMap::new(),
+                fields: BTreeMap::new(),
+
+                timestamp: chrono::Utc::now(),
+            }
+        }
+
+        pub(crate) fn timestamp(&mut self, timestamp: DateTime) -> &mut Self
+        {
+            self.timestamp = timestamp; self
+        }
+
+        pub(crate) fn tag(&mut self, key: &str, value: &str) -> &mut Self
+        {
+            self.tags.insert(key.to_owned(), value.to_owned());
+            self
+        }
+
+        pub(crate) fn field<V: Into<Value>>(&mut self, key: &str, value: V) -> &mut Self
+        {
+            self.fields.insert(key.to_owned(), value.into());
+            self
+        }
+
+        #[allow(deprecated)]
+        pub(crate) fn to_line(&self, precision: &Precision) -> String
+        {
+            let mut line = self.name.to_owned();
+
+            if ! self.tags.is_empty()
+            {
+                let tagline = self.tags.iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                line += ",";
+                line += &tagline;
+            }
+
+            if ! self.fields.is_empty()
+            {
+                let fieldline = self.fields.iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                line += " ";
+                line += &fieldline;
+            }
+
+            line += " ";
+
+            match precision
+            {
+                Precision::Nanoseconds  => { line += &self.timestamp.timestamp_nanos().to_string();  }
+                Precision::Microseconds => { line += &(self.timestamp.timestamp_nanos() * 1000).to_string(); }
+                Precision::Milliseconds => { line += &self.timestamp.timestamp_millis().to_string(); }
+                Precision::Seconds      => { line += &(self.timestamp.timestamp() ).to_string(); }
+            }
+
+            line
+        }
+    }
+
+
+    pub(crate) struct Record
+    {
+        pub(crate) org:          String,
+        pub(crate) bucket:       String,
+        pub(crate) precision:    Precision,
+        pub(crate) measurements: Vec<Measurement>,
+    }
+
+
+    impl Record
+    {
+        pub(crate) fn new(org: &str, bucket: &str) -> Self
+        {
+            Self {
+                org:          org.to_owned(),
+                bucket:       bucket.to_owned(),
+                precision:    Precision::default(),
+                measurements: Vec::new()
+            }
+        }
+
+        pub(crate) fn precision(mut self, precision: Precision) -> Self
+        {
+            self.precision = precision; self
+        }
+
+        pub(crate) fn measurement<'r>(&'r mut self, name: &str) -> &'r mut Measurement
+        {
+            self.measurements.push(Measurement::new(name));
+            self.measurements.last_mut().unwrap()
+        }
+
+        pub(crate) fn to_lines(&self) -> Vec<String>
+        {
+            let mut lines = Vec::new();
+
+            for measurement in self.measurements.iter() {
+                lines.push(measurement.to_line(&self.precision));
+            }
+
+            lines
+        }
+
+        pub(crate) fn to_line_buffer(&self) -> String
+        {
+            self.to_lines()
+                .join("\n")
+        }
+
+        pub(crate) fn to_write_request(&self, mut builder: ReqwRequestBuilder) -> InfluxResult<ReqwRequestBuilder>
+        {
+            // buffer compression
+            let mut gzipenc = FlateGzipBuilder::new()
+                .write(Vec::new(), FlateLevel::default());
+
+            gzipenc.write_all(self.to_line_buffer().as_bytes())?;
+
+            let buffer = gzipenc.finish()?;
+
+            // headers and query path
+            builder = builder.header("Content-Encoding", "gzip");
+
+            builder = builder.query(&[
+                ("org",       &self.org),
+                ("bucket",    &self.bucket),
+                ("precision", &self.precision.to_string()),
+            ]);
+
+            // buffer body
+            Ok(builder.body(buffer))
+        }
+    }
+}
+
+
+/// Counterpart of the baseline's write request, with the lines streamed into the gzip encoder
+fn streamed(rec: &Record, mut builder: ReqwRequestBuilder) -> ReqwRequestBuilder
+{
+    let mut gzipenc = FlateGzipBuilder::new()
+        .write(Vec::new(), FlateLevel::default());
+
+    {
+        let mut writer = BufWriter::new(&mut gzipenc);
+
+        rec.write_lines(&mut writer).unwrap();
+        writer.flush().unwrap();
+    }
+
+    let buffer = gzipenc.finish().unwrap();
+
+    builder = builder.header("Content-Encoding", "gzip");
+
+    builder = builder.query(&[
+        ("org",       "org"),
+        ("bucket",    "bucket"),
+        ("precision", &Precision::Milliseconds.to_string()),
+    ]);
+
+    builder.body(buffer)
+}
+
+
+fn encoding(c: &mut Criterion)
+{
+    let rec      = record();
+    let baseline = baseline_record();
+
+    let client = reqwest::blocking::Client::new();
+    let url    = "http://127.0.0.1:8086/api/v2/write";
+
+    let mut group = c.benchmark_group("encode 50k points");
+
+    group.sample_size(20);
+
+    group.bench_function("per line strings + join", |b| b.iter(|| black_box(&baseline).to_line_buffer()));
+
+    group.bench_function("streamed into buffer", |b| b.iter(|| {
+        let mut buffer = Vec::new();
+        black_box(&rec).write_lines(&mut buffer).unwrap();
+        buffer
+    }));
+
+    group.finish();
+
+    let mut group = c.benchmark_group("encode and compress 50k points");
+
+    group.sample_size(20);
+
+    group.bench_function("per line strings + join + gzip", |b| b.iter(|| {
+        black_box(&baseline).to_write_request(client.post(url)).unwrap()
+    }));
+
+    group.bench_function("streamed into gzip", |b| b.iter(|| streamed(black_box(&rec), client.post(url))));
+
+    group.finish();
+}
+
+
+criterion_group!(benches, encoding);
+criterion_main!(benches);
@@ -18,7 +18,7 @@ fn main() -> Result<(), InfluxError>
     let creds   = Credentials::from_basic("testuser", "testpasswd");
     let backlog = FileBacklog::new("./ignore/backlog")?;
 
-    let mut client = Client::build("http://127.0.0.1:8086".into(), creds)
+    let client = Client::build("http://127.0.0.1:8086".into(), creds)
         .backlog(backlog)
         .finish()?;
 
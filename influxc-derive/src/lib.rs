@@ -0,0 +1,397 @@
+/*!
+# Derive Macro for influxc
+
+Provides `#[derive(InfluxMeasurement)]`, implementing `influxc::IntoMeasurement` for structs with named fields, so
+they can be pushed into a `influxc::Record` with a single call. Enable it through the `derive` feature of `influxc`
+rather than depending on this crate directly.
+*/
+#![deny(missing_docs)]
+
+use proc_macro::TokenStream;
+
+use proc_macro2::Span;
+use proc_macro2::TokenStream as TokenStream2;
+
+use quote::quote;
+
+use syn::Data;
+use syn::Type;
+use syn::Fields;
+use syn::LitStr;
+use syn::Attribute;
+use syn::DeriveInput;
+use syn::parse_macro_input;
+use syn::spanned::Spanned;
+
+
+/// Implement `influxc::IntoMeasurement` for a struct with named fields.
+///
+/// Fields become measurement fields unless annotated otherwise:
+///
+/// - `#[influx(tag)]`: Becomes a tag. The type has to implement `ToString`.
+/// - `#[influx(field)]`: Becomes a field, which is the default. The type has to implement
+///   `influxc::IntoFieldValue`, so `Option` members are left out when `None`.
+/// - `#[influx(timestamp)]`: Becomes the timestamp of the measurement. The type has to implement
+///   `influxc::IntoTimestamp`, or be an `Option` of such a type, leaving the timestamp to the server when `None`.
+/// - `#[influx(rename = "name")]`: Uses a different tag or field key than the Rust field name.
+/// - `#[influx(skip)]`: Is left out.
+///
+/// The measurement is named after the struct, unless renamed with `#[influx(rename = "name")]` on the struct.
+///
+/// ## Examples
+///
+/// ```rust
+/// use influxc::Record;
+/// use influxc::Precision;
+/// use influxc::InfluxMeasurement;
+///
+/// use chrono::TimeZone;
+///
+/// #[derive(InfluxMeasurement)]
+/// #[influx(rename = "sensor")]
+/// struct Sensor
+/// {
+///     #[influx(tag)]
+///     floor: u8,
+///
+///     #[influx(tag, rename = "side")]
+///     exposure: String,
+///
+///     temp: f64,
+///
+///     #[influx(field)]
+///     brightness: i64,
+///
+///     #[influx(skip)]
+///     _calibration: Vec<f64>,
+///
+///     #[influx(timestamp)]
+///     taken: chrono::DateTime<chrono::Utc>,
+/// }
+///
+/// let mut rec = Record::new("org", "bucket")
+///     .precision(Precision::Seconds);
+///
+/// rec.push(Sensor {
+///     floor:        2,
+///     exposure:     "west".to_owned(),
+///     temp:         21.5,
+///     brightness:   500,
+///     _calibration: vec![1.0, 0.5],
+///     taken:        chrono::Utc.timestamp_opt(1556813561, 0).unwrap(),
+/// });
+///
+/// let mut buffer = Vec::new();
+/// rec.write_lines(&mut buffer).unwrap();
+///
+/// assert_eq!(buffer, b"sensor,floor=2,side=west brightness=500i,temp=21.5 1556813561\n");
+/// ```
+///
+/// An `Option` timestamp leaves it to the server when `None`:
+///
+/// ```rust
+/// use influxc::Record;
+/// use influxc::Precision;
+/// use influxc::InfluxMeasurement;
+///
+/// use std::time::Duration;
+/// use std::time::SystemTime;
+///
+/// #[derive(InfluxMeasurement)]
+/// struct Door
+/// {
+///     open: bool,
+///
+///     #[influx(timestamp)]
+///     at: Option<SystemTime>,
+/// }
+///
+/// let mut rec = Record::new("org", "bucket")
+///     .precision(Precision::Seconds);
+///
+/// rec.push(Door {open: true,  at: None});
+/// rec.push(Door {open: false, at: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1556813561))});
+///
+/// let mut buffer = Vec::new();
+/// rec.write_lines(&mut buffer).unwrap();
+///
+/// assert_eq!(buffer, b"Door open=true\nDoor open=false 1556813561\n");
+/// ```
+#[proc_macro_derive(InfluxMeasurement, attributes(influx))]
+pub fn derive_influx_measurement(input: TokenStream) -> TokenStream
+{
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input)
+    {
+        Ok(tokens) => { tokens.into() }
+        Err(e)     => { e.to_compile_error().into() }
+    }
+}
+
+
+/// Role a struct member plays in the measurement
+#[derive(Clone, Copy, PartialEq)]
+enum Role
+{
+    Tag,
+    Field,
+    Timestamp,
+    Skip,
+}
+
+
+/// Parsed `#[influx(...)]` annotations of a struct or one of its members, along with where they were given
+struct Options
+{
+    role:   Option<(Role, Span)>,
+    rename: Option<LitStr>,
+}
+
+
+const NAMED_FIELDS_ONLY: &str = "InfluxMeasurement requires a struct with named fields";
+
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2>
+{
+    let fields = match &input.data
+    {
+        Data::Struct(data) => {
+            match &data.fields
+            {
+                Fields::Named(named)     => { &named.named }
+                Fields::Unnamed(unnamed) => { return Err(syn::Error::new_spanned(unnamed, NAMED_FIELDS_ONLY)); }
+                Fields::Unit             => { return Err(syn::Error::new_spanned(&input.ident, NAMED_FIELDS_ONLY)); }
+            }
+        }
+
+        _ => { return Err(syn::Error::new_spanned(&input.ident, "InfluxMeasurement can only be derived for structs")); }
+    };
+
+    let opts = options(&input.attrs)?;
+
+    if let Some((role, span)) = opts.role
+    {
+        let msg = match role
+        {
+            Role::Skip => { "skip is not supported on the struct itself" }
+            _          => { "only rename is supported on the struct itself" }
+        };
+
+        return Err(syn::Error::new(span, msg));
+    }
+
+    let name = opts.rename
+        .map(|rename| rename.value())
+        .unwrap_or_else(|| input.ident.to_string());
+
+    let mut fills     = Vec::new();
+    let mut timestamp = None;
+
+    for field in fields.iter()
+    {
+        let opts  = options(&field.attrs)?;
+        let ident = field.ident.as_ref().unwrap();
+        let key   = opts.rename.as_ref().map(LitStr::value).unwrap_or_else(|| ident.to_string());
+
+        match opts.role.map(|(role, _)| role).unwrap_or(Role::Field)
+        {
+            Role::Skip => {}
+
+            Role::Tag => {
+                fills.push(quote! {
+                    measurement.tag(#key, &::std::string::ToString::to_string(&self.#ident));
+                });
+            }
+
+            Role::Field => {
+                fills.push(quote! {
+                    measurement.field(#key, self.#ident);
+                });
+            }
+
+            Role::Timestamp => {
+                if timestamp.is_some() {
+                    return Err(syn::Error::new_spanned(ident, "only one member can be the timestamp"));
+                }
+
+                if let Some(rename) = &opts.rename {
+                    return Err(syn::Error::new(rename.span(), "rename has no effect on the timestamp"));
+                }
+
+                timestamp = if is_option(&field.ty) {
+                    Some(quote! {
+                        match self.#ident
+                        {
+                            ::std::option::Option::Some(timestamp) => { measurement.timestamp(timestamp); }
+                            ::std::option::Option::None            => { measurement.server_timestamp(); }
+                        }
+                    })
+                } else {
+                    Some(quote! {
+                        measurement.timestamp(self.#ident);
+                    })
+                };
+            }
+        }
+    }
+
+    let ident = &input.ident;
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::influxc::IntoMeasurement for #ident #ty_generics #where_clause
+        {
+            fn measurement_name(&self) -> &str
+            {
+                #name
+            }
+
+            fn fill_measurement(self, measurement: &mut ::influxc::Measurement)
+            {
+                #(#fills)*
+                #timestamp
+            }
+        }
+    })
+}
+
+
+/// Whether the type is spelled as an `Option`, which is all a derive macro is able to tell
+fn is_option(ty: &Type) -> bool
+{
+    match ty
+    {
+        Type::Path(path) => {
+            path.qself.is_none() && path.path.segments.last().map(|segment| segment.ident == "Option").unwrap_or(false)
+        }
+
+        _ => { false }
+    }
+}
+
+
+fn options(attrs: &[Attribute]) -> syn::Result<Options>
+{
+    let mut opts = Options {role: None, rename: None};
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("influx"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let role = if meta.path.is_ident("tag") {
+                Role::Tag
+            }
+            else if meta.path.is_ident("field") {
+                Role::Field
+            }
+            else if meta.path.is_ident("timestamp") {
+                Role::Timestamp
+            }
+            else if meta.path.is_ident("skip") {
+                Role::Skip
+            }
+            else if meta.path.is_ident("rename") {
+                opts.rename = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+            else {
+                return Err(meta.error("unknown influx attribute, expected one of: tag, field, timestamp, rename, skip"));
+            };
+
+            if opts.role.is_some() {
+                return Err(meta.error("only one of tag, field, timestamp or skip may be given"));
+            }
+
+            opts.role = Some((role, meta.path.span()));
+
+            Ok(())
+        })?;
+    }
+
+    Ok(opts)
+}
+
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+
+    /// Message of the error deriving for the struct, along with the source text it points to
+    fn error(source: &str) -> (String, String)
+    {
+        let input = syn::parse_str::<DeriveInput>(source).unwrap();
+        let error = expand(&input).expect_err("Expansion succeeded");
+
+        (error.to_string(), error.span().source_text().unwrap_or_default())
+    }
+
+
+    fn expected(message: &str, text: &str) -> (String, String)
+    {
+        (message.to_owned(), text.to_owned())
+    }
+
+
+    #[test]
+    fn rejects_unknown_attribute()
+    {
+        assert_eq!(error("struct Sensor { #[influx(tags)] floor: u8, temp: f64 }"),
+            expected("unknown influx attribute, expected one of: tag, field, timestamp, rename, skip", "tags"));
+    }
+
+
+    #[test]
+    fn rejects_second_timestamp()
+    {
+        let source = "struct Sensor { temp: f64, #[influx(timestamp)] taken: i64, #[influx(timestamp)] stored: i64 }";
+
+        assert_eq!(error(source), expected("only one member can be the timestamp", "stored"));
+    }
+
+
+    #[test]
+    fn rejects_roles_on_struct()
+    {
+        assert_eq!(error("#[influx(tag)] struct Sensor { temp: f64 }"),
+            expected("only rename is supported on the struct itself", "tag"));
+
+        assert_eq!(error("#[influx(rename = \"sensor\", skip)] struct Sensor { temp: f64 }"),
+            expected("skip is not supported on the struct itself", "skip"));
+    }
+
+
+    #[test]
+    fn rejects_structs_without_named_fields()
+    {
+        assert_eq!(error("struct Sensor(u8, f64);"), expected(NAMED_FIELDS_ONLY, "(u8, f64)"));
+        assert_eq!(error("struct Sensor;"),          expected(NAMED_FIELDS_ONLY, "Sensor"));
+
+        assert_eq!(error("enum Sensor { Floor(u8) }"),
+            expected("InfluxMeasurement can only be derived for structs", "Sensor"));
+    }
+
+
+    #[test]
+    fn rejects_renamed_timestamp()
+    {
+        assert_eq!(error("struct Sensor { temp: f64, #[influx(timestamp, rename = \"time\")] taken: i64 }"),
+            expected("rename has no effect on the timestamp", "\"time\""));
+    }
+
+
+    #[test]
+    fn tells_option_timestamps()
+    {
+        let ty = |source| syn::parse_str::<Type>(source).unwrap();
+
+        assert!(is_option(&ty("Option<chrono::DateTime<chrono::Utc>>")));
+        assert!(is_option(&ty("std::option::Option<std::time::SystemTime>")));
+
+        assert!(! is_option(&ty("std::time::SystemTime")));
+        assert!(! is_option(&ty("(i64, Precision)")));
+    }
+}
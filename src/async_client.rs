@@ -0,0 +1,374 @@
+//!
+//! Async Client Connection and Interface to Database
+//!
+use crate::Record;
+use crate::Credentials;
+use crate::ClientBuilder;
+
+use crate::AsyncBacklog;
+
+use crate::writing;
+use crate::writing::WriteOptions;
+use crate::transport::Transport;
+
+use crate::ChunkError;
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use crate::ReqwUrl;
+use crate::ReqwMethod;
+use crate::ReqwAsyncClient;
+
+use std::time::Instant;
+
+
+/// Counterpart of [Client](struct.Client.html) for async runtimes like Tokio, available with the `async` feature. It
+/// behaves the same, without blocking the executor. Assemble it through
+/// [ClientBuilder::finish_async](struct.ClientBuilder.html#method.finish_async).
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use influxc::Record;
+/// use influxc::AsyncClient;
+/// use influxc::Credentials;
+/// use influxc::InfluxError;
+///
+/// async fn report(load: f64) -> Result<(), InfluxError>
+/// {
+///     let creds = Credentials::from_token("TOKEN");
+///
+///     let mut client = AsyncClient::build("http://127.0.0.1:8086".into(), creds)
+///         .finish_async()
+///         .await?;
+///
+///     let mut rec = Record::new("org", "bucket");
+///
+///     rec.measurement("cpu").field("load", load);
+///
+///     client.write(&rec).await
+/// }
+/// ```
+#[derive(Debug)]
+pub struct AsyncClient
+{
+    url:    ReqwUrl,
+    creds:  Credentials,
+    client: ReqwAsyncClient,
+
+    backlog: Box<dyn AsyncBacklog>,
+
+    pub(crate) org:     Option<String>,
+    pub(crate) options: WriteOptions,
+}
+
+
+impl AsyncClient
+{
+    /// Create a builder to parametrize and construct this [AsyncClient](struct.AsyncClient.html).
+    pub fn build(url: String, creds: Credentials) -> ClientBuilder
+    {
+        ClientBuilder::new(url, creds)
+    }
+
+    /// Directly construct this [AsyncClient](struct.AsyncClient.html), with default TLS and HTTP settings.
+    pub async fn new(url: String, creds: Credentials, backlog: Box<dyn AsyncBacklog>) -> InfluxResult<Self>
+    {
+        Self::assemble(url, creds, backlog, &Transport::default(), WriteOptions::default()).await
+    }
+
+    /// Construct the client as configured on the [ClientBuilder](struct.ClientBuilder.html)
+    pub(crate) async fn assemble(url: String, creds: Credentials, backlog: Box<dyn AsyncBacklog>, transport: &Transport,
+        options: WriteOptions) -> InfluxResult<Self>
+    {
+        let client = transport.asynchronous()?;
+
+        let url = match ReqwUrl::parse(&url)
+        {
+            Ok(url) => { url }
+            Err(e)  => { return Err(format!("Failed to parse URL: {} due to {}", url, e).into()) }
+        };
+
+        let mut this = Self {client, url, creds, backlog, org: None, options};
+
+        this.authenticate().await?;
+
+        Ok(this)
+    }
+
+    /// Organization set on the [ClientBuilder](struct.ClientBuilder.html), or taken from the environment or influx CLI
+    /// config it was created from
+    pub fn org(&self) -> Option<&str>
+    {
+        self.org.as_deref()
+    }
+
+    /// Submit a [Record](struct.Record.html) to be written to InfluxDB. Or backlogged if you set a backlogger. See
+    /// [Client::write](struct.Client.html#method.write).
+    pub async fn write(&mut self, record: &Record) -> InfluxResult<()>
+    {
+        let record = self.options.prepare(record)?;
+
+        if let Err(e) = self.write_backlog().await {
+            self.write_pending(&record).await?; Err(e)
+        }
+        else
+        {
+            let mut errors = Vec::new();
+
+            for (chunk, error) in self.write_chunks(&record).await?
+            {
+                if ! matches!(error.error(), InfluxError::WriteOversized(_)) {
+                    self.write_pending(&chunk).await?;
+                }
+
+                errors.push(error);
+            }
+
+            writing::chunk_result(&record, errors)
+        }
+    }
+
+    /// Submit pending/backlogged [Records](struct.Record.html) to writing. It will attempt to flush them to database.
+    pub async fn flush(&mut self) -> InfluxResult<()>
+    {
+        self.write_backlog().await
+    }
+}
+
+
+/// Private interface
+impl AsyncClient
+{
+    async fn write_backlog(&mut self) -> InfluxResult<()>
+    {
+        let records = self.backlog.read_pending().await?;
+
+        if ! records.is_empty() {
+            info!("Found {} backlogged entries, attempting to commit", records.len());
+        }
+
+        for record in records.iter()
+        {
+            let replay = writing::replay(record, self.write_chunks(record).await?);
+
+            if replay.result.is_ok() || replay.rewrite
+            {
+                let result = self.backlog.truncate_pending(record).await;
+
+                if let Err(e) = result
+                {
+                    let msg = format!("Failed to eliminate/truncate record from backlog: {}", e);
+                    error!("{}", msg);
+                    panic!("{}", msg);
+                }
+
+                for chunk in replay.keep.iter() {
+                    self.backlog.write_pending(chunk).await?;
+                }
+            }
+
+            replay.result?;
+        }
+
+        Ok(())
+    }
+
+    async fn write_pending(&mut self, record: &Record) -> InfluxResult<()>
+    {
+        let record = self.options.pending(record);
+
+        self.backlog.write_pending(&record).await
+    }
+
+    async fn write_chunks(&mut self, record: &Record) -> InfluxResult<Vec<(Record, ChunkError)>>
+    {
+        let mut chunks = self.options.chunks(record)?;
+
+        while let Some((range, chunk)) = chunks.next()
+        {
+            let result = self.write_retrying(&chunk).await;
+
+            chunks.report(&self.options, range, chunk, result)?;
+        }
+
+        Ok(chunks.into_failed())
+    }
+
+    /// Write the record, retrying transient failures as the retry policy dictates
+    async fn write_retrying(&self, record: &Record) -> InfluxResult<()>
+    {
+        let started      = Instant::now();
+        let mut attempts = 0;
+
+        loop
+        {
+            let result = self.write_record(record).await;
+
+            attempts += 1;
+
+            if let (Err(e), Some(policy)) = (&result, &self.options.retry)
+            {
+                if let Some(delay) = policy.next_delay(attempts, e, started.elapsed())
+                {
+                    warn!("Write attempt {} failed ({}), retrying in {:?}", attempts, e, delay);
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            }
+
+            return result;
+        }
+    }
+
+    async fn write_record(&self, record: &Record) -> InfluxResult<()>
+    {
+        let mut url = self.url.clone();
+
+        let request = self.options.write_request(record, &self.creds)?;
+
+        url.set_path(request.path);
+
+        let (body, gzipped) = record.to_write_body(&self.options.default_tags, self.options.compression)?;
+
+        debug!("Request: POST {}", request);
+
+        let mut builder = self.client.request(ReqwMethod::POST, url)
+            .header("Accept-Encoding", "gzip")
+            .query(&request.query);
+
+        if let Some((header, value)) = request.auth {
+            builder = builder.header(header, value);
+        }
+
+        if gzipped {
+            builder = builder.header("Content-Encoding", "gzip");
+        }
+
+        builder = builder.body(body);
+
+        let reply   = builder.send().await?;
+        let status  = reply.status().as_u16();
+        let headers = reply.headers().clone();
+        let body    = reply.bytes().await?;
+
+        self.options.write_reply(status, &headers, &body)?;
+
+        info!("Written: {}", record);
+
+        Ok(())
+    }
+
+    async fn authenticate(&mut self) -> InfluxResult<()>
+    {
+        // 1.x has no sign in, but takes the credentials along with every request
+        if self.options.v1.is_some() {
+            return Ok(());
+        }
+
+        if let Some(auth) = self.creds.signin_header()
+        {
+            let mut url = self.url.clone();
+
+            url.set_path("/api/v2/signin");
+
+            debug!("Request: POST {}", url.path());
+
+            let req = self.client.request(ReqwMethod::POST, url)
+                .header("Accept-Encoding", "gzip")
+                .header("Authorization", auth);
+
+            let rep     = req.send().await?;
+            let status  = rep.status().as_u16();
+            let headers = rep.headers().clone();
+            let body    = rep.bytes().await?;
+
+            self.creds.signin_reply(status, &headers, &writing::decode_body(&headers, &body)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Backlog;
+    use crate::Compression;
+
+    use crate::stub::serve;
+    use crate::stub::MemoryBacklog;
+
+
+    async fn client(url: String, backlog: MemoryBacklog) -> AsyncClient
+    {
+        AsyncClient::build(url, Credentials::from_token("TOKEN"))
+            .compression(Compression::Off)
+            .backlog(backlog)
+            .finish_async()
+            .await
+            .unwrap()
+    }
+
+
+    fn record(load: f64) -> Record
+    {
+        let mut rec = Record::new("org", "bucket").server_timestamps();
+
+        rec.measurement("cpu").field("load", load);
+
+        rec
+    }
+
+
+    #[tokio::test]
+    async fn writes_record()
+    {
+        let (url, bodies) = serve(vec![204]);
+
+        let backlog    = MemoryBacklog::default();
+        let mut client = client(url, backlog.clone()).await;
+
+        client.write(&record(0.5)).await.unwrap();
+
+        assert_eq!(bodies.recv().unwrap(), "cpu load=0.5\n");
+        assert!(backlog.lines().is_empty());
+    }
+
+
+    #[tokio::test]
+    async fn backlogs_unready_write()
+    {
+        let (url, bodies) = serve(vec![503]);
+
+        let backlog    = MemoryBacklog::default();
+        let mut client = client(url, backlog.clone()).await;
+
+        assert!(matches!(client.write(&record(0.5)).await, Err(InfluxError::WriteUnready(_))));
+        assert_eq!(bodies.recv().unwrap(), "cpu load=0.5\n");
+        assert_eq!(backlog.lines(), vec!["cpu load=0.5\n"]);
+    }
+
+
+    #[tokio::test]
+    async fn replays_backlog_on_flush()
+    {
+        let (url, bodies) = serve(vec![204, 204]);
+
+        let mut backlog = MemoryBacklog::default();
+
+        Backlog::write_pending(&mut backlog, &record(0.5)).unwrap();
+        Backlog::write_pending(&mut backlog, &record(0.7)).unwrap();
+
+        let mut client = client(url, backlog.clone()).await;
+
+        client.flush().await.unwrap();
+
+        assert_eq!(bodies.recv().unwrap(), "cpu load=0.5\n");
+        assert_eq!(bodies.recv().unwrap(), "cpu load=0.7\n");
+        assert!(backlog.lines().is_empty());
+    }
+}
@@ -1,6 +1,14 @@
 //!
 //! Basic and Token Authentication Credentials
 //!
+use crate::json;
+use crate::b64;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+use crate::ApiGenericError;
+
+use crate::ReqwHeaderMap;
 
 
 /// Credentials used to authenticate at the InfluxDB server
@@ -48,3 +56,73 @@ impl Credentials
         }
     }
 }
+
+
+/// Interface shared by the blocking and async clients
+impl Credentials
+{
+    /// Authorization header for signing in at the server, if these are basic credentials without session yet
+    pub(crate) fn signin_header(&self) -> Option<String>
+    {
+        match self
+        {
+            Credentials::Basic{user, passwd, cookie: None} => {
+                Some(format!("Basic {}", b64::encode(format!("{}:{}", user, passwd))))
+            }
+
+            _ => { None }
+        }
+    }
+
+    /// Keep the session cookie handed out by a successful sign in, or map the failure onto an error
+    pub(crate) fn signin_reply(&mut self, status: u16, headers: &ReqwHeaderMap, body: &[u8]) -> InfluxResult<()>
+    {
+        match status
+        {
+            204 => {
+                if let Some(cookie) = headers.get("Set-Cookie")
+                {
+                    let session = {
+                        if let Ok(s) = cookie.to_str() {
+                            s.to_owned()
+                        } else {
+                            return Err(format!("Failed to extract session cookie string: {:#?}", cookie).into());
+                        }
+                    };
+
+                    if let Credentials::Basic{cookie, ..} = self {
+                        *cookie = Some(session);
+                    }
+
+                    Ok(())
+                }
+                else {
+                    Err("Missing session cookie after successfull basic auth".into())
+                }
+            }
+
+            401 => { Err(InfluxError::AuthUnauthorized(json::from_slice::<ApiGenericError>(body)?)) }
+            403 => { Err(InfluxError::AuthAccountDisabled(json::from_slice::<ApiGenericError>(body)?)) }
+            _   => { Err(InfluxError::AuthUnknown(json::from_slice::<ApiGenericError>(body)?)) }
+        }
+    }
+
+    /// Header authorizing requests, either the session cookie from basic auth or the token
+    pub(crate) fn header(&self) -> InfluxResult<(&'static str, String)>
+    {
+        match self
+        {
+            Credentials::Basic{user: _, passwd: _, cookie: None} => {
+                Err("Missing session cookie from basic auth. This should not have happened!".into())
+            }
+
+            Credentials::Basic{user: _, passwd: _, cookie: Some(session)} => {
+                Ok(("Cookie", session.clone()))
+            }
+
+            Credentials::Token{token} => {
+                Ok(("Authorization", format!("Token {}", token)))
+            }
+        }
+    }
+}
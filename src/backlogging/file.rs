@@ -8,6 +8,8 @@
 use super::Backlog;
 
 use crate::Record;
+use crate::Target;
+use crate::Rounding;
 use crate::Precision;
 
 use crate::InfluxError;
@@ -60,11 +62,26 @@ impl FileBacklog
         let listing = std::fs::read_dir(&dir)
             .annotate(format!("While opening backlog directory: {:#?}", dir))?;
 
+        let mut paths = Vec::new();
+
         for entry in listing
         {
-            let entry = entry?;
-            let path  = entry.path();
-            let file  = Archive::open(&path)?;
+            let path    = entry?.path();
+            let current = dir.join(ArchiveMeta::from_path(&path)?.to_path());
+
+            if path != current {
+                migrate(&path, &current)?;
+            }
+
+            if ! paths.contains(&current) {
+                paths.push(current);
+            }
+        }
+
+        // only opened once all archives named the old way have been merged into their current ones
+        for path in paths
+        {
+            let file = Archive::open(&path)?;
 
             archives.insert(path, file);
         }
@@ -75,7 +92,7 @@ impl FileBacklog
     fn archive(&mut self, record: &Record) -> InfluxResult<&mut Archive>
     {
         let meta = ArchiveMeta::from_record(record);
-        let path = meta.to_path();
+        let path = self.dir.join(meta.to_path());
 
         if ! self.archives.contains_key(&path) {
             self.archives.insert(path.clone(), Archive::open(&path)?);
@@ -126,6 +143,7 @@ impl Backlog for FileBacklog
 #[derive(Debug)]
 struct Archive
 {
+    path:   PathBuf,
     meta:   ArchiveMeta,
     handle: Option<File>,
     count:  usize,
@@ -136,12 +154,12 @@ impl Archive
 {
     pub fn open(path: &Path) -> InfluxResult<Self>
     {
-        let meta   = ArchiveMeta::from_path(&path)?;
-        let handle = open(&path, false)?;
+        let meta   = ArchiveMeta::from_path(path)?;
+        let handle = open(path, false)?;
         let bfrd   = BufReader::new(&handle);
         let count  = bfrd.lines().count();
 
-        Ok(Self {meta, handle: Some(handle), count})
+        Ok(Self {path: path.to_owned(), meta, handle: Some(handle), count})
     }
 
     pub fn record(&mut self) -> InfluxResult<Option<Record>>
@@ -176,8 +194,9 @@ impl Archive
                     }
                 }
 
-                let mut record = Record::new(&self.meta.org, &self.meta.bucket)
-                    .precision(self.meta.precision.clone());
+                let mut record = Record::targeting(self.meta.org.clone(), self.meta.bucket.clone())
+                    .precision(self.meta.precision.clone())
+                    .rounding(self.meta.rounding);
 
                 record.measurements = msrmts;
 
@@ -198,9 +217,14 @@ impl Archive
         {
             let mut writer = BufWriter::new(handle);
 
+            // default tags get persisted merged into every measurement, so replays encode exactly the same
             for msrmt in record.measurements.iter()
             {
-                let line = json::to_string(msrmt)?;
+                let line = if record.default_tags.is_empty() {
+                    json::to_string(msrmt)?
+                } else {
+                    json::to_string(&msrmt.with_tags(&record.default_tags))?
+                };
 
                 writer.write_all(line.as_bytes())?;
                 writer.write_all(b"\n")?;
@@ -219,7 +243,7 @@ impl Archive
 
     pub fn truncate(&mut self) -> InfluxResult<()>
     {
-        std::fs::remove_file(&self.meta.to_path())?;  // to keep dir as clean as possible from empty backlogs
+        std::fs::remove_file(&self.path)?;  // to keep dir as clean as possible from empty backlogs
 
         self.handle = None;
         self.count  = 0;
@@ -230,7 +254,7 @@ impl Archive
     fn prepare_handle(&mut self, seek: Option<SeekFrom>) -> InfluxResult<()>
     {
         if self.handle.is_none() {
-            self.handle = Some(open(&self.meta.to_path(), false)?);
+            self.handle = Some(open(&self.path, false)?);
         }
 
         let handle = self.handle.as_mut().unwrap();
@@ -244,6 +268,33 @@ impl Archive
 }
 
 
+/// Move an archive named the old way to its current name, appending to the archive there should there be one already
+fn migrate(from: &Path, to: &Path) -> InfluxResult<()>
+{
+    info!("Renaming backlog archive {:#?} to {:#?}", from, to);
+
+    if to.exists()
+    {
+        let lines = std::fs::read(from)
+            .annotate(format!("While reading file: {:#?}", from))?;
+
+        OpenOptions::new()
+            .append(true)
+            .open(to)
+            .and_then(|mut file| file.write_all(&lines))
+            .annotate(format!("While appending to file: {:#?}", to))?;
+
+        std::fs::remove_file(from)
+            .annotate(format!("While removing file: {:#?}", from))
+    }
+    else
+    {
+        std::fs::rename(from, to)
+            .annotate(format!("While renaming file: {:#?}", from))
+    }
+}
+
+
 fn open(path: &Path, truncate: bool) -> InfluxResult<File>
 {
     OpenOptions::new()
@@ -259,12 +310,18 @@ fn open(path: &Path, truncate: bool) -> InfluxResult<File>
 #[derive(Debug)]
 struct ArchiveMeta
 {
-    org:       String,  // TODO Cow<'s, &'s str>
-    bucket:    String,  // TODO Cow<'s, &'s str>
+    org:       Target,
+    bucket:    Target,
     precision: Precision,
+    rounding:  Rounding,
 }
 
 
+/// Prefix of archive names whose parts are length prefixed, telling them from the ones joined by underscores before,
+/// which broke on names containing underscores. It is not part of the base32 alphabet.
+const PREFIXED: &str = "v2-";
+
+
 impl ArchiveMeta
 {
     fn from_record(record: &Record) -> Self
@@ -273,39 +330,275 @@ impl ArchiveMeta
             org:       record.org.clone(),
             bucket:    record.bucket.clone(),
             precision: record.precision.clone(),
+            rounding:  record.rounding,
         }
     }
 
     fn from_path(path: &Path) -> InfluxResult<Self>
     {
         let stem = path.file_stem()
+            .and_then(|stem| stem.to_str())
             .ok_or_else::<InfluxError, _>(|| format!("Could not extract file stem from: {:#?}", path).into())?;
 
-        let dec32 = b32::decode(b32::Alphabet::RFC4648 {padding: false}, stem.to_str().unwrap())
+        let (encoded, prefixed) = match stem.strip_prefix(PREFIXED)
+        {
+            Some(encoded) => { (encoded, true) }
+            None          => { (stem, false) }
+        };
+
+        let dec32 = b32::decode(b32::Alphabet::RFC4648 {padding: false}, encoded)
             .ok_or_else::<InfluxError, _>(|| format!("Could not base32 decode file name for its parts: {:#?}", path).into())?;
 
         let name = String::from_utf8(dec32)
             .map_err(|e| InfluxError::Error(format!("Invalid UTF8 while decoding archive path '{:#?}': {}", path, e)))?;
 
-        let parts = name.split('_')
-            .collect::<Vec<&str>>();
-
-        if parts.len() < 3 {
-            Err(format!("Could not determine archive name from path: {:#?}", path).into())
+        if prefixed {
+            Self::from_prefixed_name(&name, path)
         } else {
-            let org       = parts[0].to_owned();
-            let bucket    = parts[1].to_owned();
-            let precision = parts[2].parse()?;
+            Self::from_joined_name(&name, path)
+        }
+    }
 
-            Ok(ArchiveMeta {org, bucket, precision})
+    /// Parts of the name each prefixed by their length and a colon: org, bucket, precision, rounding and forms, the
+    /// latter telling name (n) from ID (i) for org and bucket
+    fn from_prefixed_name(name: &str, path: &Path) -> InfluxResult<Self>
+    {
+        let malformed = || format!("Could not determine archive name from path: {:#?}", path);
+
+        let mut parts = Vec::new();
+        let mut rest  = name;
+
+        while ! rest.is_empty()
+        {
+            let (len, tail) = rest.split_once(':')
+                .ok_or_else(malformed)?;
+
+            let len = len.parse::<usize>()
+                .map_err(|_| malformed())?;
+
+            match (tail.get(..len), tail.get(len..))
+            {
+                (Some(part), Some(tail)) => { parts.push(part); rest = tail; }
+                _                        => { return Err(malformed().into()); }
+            }
+        }
+
+        match parts.as_slice()
+        {
+            [org, bucket, precision, rounding, forms] => {
+                let (org, bucket) = Self::targets(org, bucket, forms, path)?;
+
+                Ok(ArchiveMeta {org, bucket, precision: precision.parse()?, rounding: rounding.parse()?})
+            }
+
+            _ => { Err(malformed().into()) }
+        }
+    }
+
+    /// Organization, bucket and precision joined by underscores, as written before they got length prefixed
+    fn from_joined_name(name: &str, path: &Path) -> InfluxResult<Self>
+    {
+        match name.split('_').collect::<Vec<&str>>().as_slice()
+        {
+            [org, bucket, precision] => {
+                Ok(ArchiveMeta {
+                    org:       Target::name(org),
+                    bucket:    Target::name(bucket),
+                    precision: precision.parse()?,
+                    rounding:  Rounding::default(),
+                })
+            }
+
+            _ => {
+                Err(format!("Could not determine archive name from path: {:#?}", path).into())
+            }
+        }
+    }
+
+    fn targets(org: &str, bucket: &str, forms: &str, path: &Path) -> InfluxResult<(Target, Target)>
+    {
+        match forms.as_bytes()
+        {
+            &[org_form, bucket_form] if [org_form, bucket_form].iter().all(|form| *form == b'n' || *form == b'i') => {
+                let target = |form, value| if form == b'i' { Target::id(value) } else { Target::name(value) };
+
+                Ok((target(org_form, org), target(bucket_form, bucket)))
+            }
+
+            _ => { Err(format!("Could not determine org and bucket forms from path: {:#?}", path).into()) }
         }
     }
 
     fn to_path(&self) -> PathBuf
     {
-        let name  = format!("{}_{}_{}", self.org, self.bucket, self.precision.to_string());
+        let form = |target: &Target| if target.is_id() { "i" } else { "n" };
+
+        let precision = self.precision.to_string();
+        let rounding  = self.rounding.to_string();
+        let forms     = format!("{}{}", form(&self.org), form(&self.bucket));
+
+        let name = [self.org.as_str(), self.bucket.as_str(), &precision, &rounding, &forms].iter()
+            .map(|part| format!("{}:{}", part.len(), part))
+            .collect::<String>();
+
         let enc32 = b32::encode(b32::Alphabet::RFC4648 {padding: false}, name.as_bytes());
 
-        PathBuf::from(format!("{}.log", enc32))
+        PathBuf::from(format!("{}{}.log", PREFIXED, enc32))
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Backlog;
+
+
+    /// Empty directory of its own for every test, removed again on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir
+    {
+        fn new(name: &str) -> Self
+        {
+            let dir = std::env::temp_dir().join(format!("influxc-backlog-{}-{}", name, std::process::id()));
+
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir
+    {
+        fn drop(&mut self)
+        {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+
+    fn meta(org: Target, bucket: Target) -> ArchiveMeta
+    {
+        ArchiveMeta {org, bucket, precision: Precision::Milliseconds, rounding: Rounding::Floor}
+    }
+
+
+    fn encode(name: &str) -> String
+    {
+        b32::encode(b32::Alphabet::RFC4648 {padding: false}, name.as_bytes())
+    }
+
+
+    /// Path of an archive named the way it was before its parts got length prefixed
+    fn joined_path(name: &str) -> PathBuf
+    {
+        PathBuf::from(format!("{}.log", encode(name)))
+    }
+
+
+    fn record(org: &str, bucket: &str, value: i64) -> Record
+    {
+        let mut rec = Record::new(org, bucket).precision(Precision::Seconds);
+
+        rec.measurement("m").field("v", value).timestamp((1_600_000_000, Precision::Seconds));
+        rec
+    }
+
+
+    #[test]
+    fn round_trips_names_with_separators()
+    {
+        let cases = [
+            (Target::name("my_org"),  Target::name("my_bucket_1")),
+            (Target::name("a:3:b"),   Target::name("_")),
+            (Target::id("0261d8287f4d6000"), Target::name("snake_case")),
+            (Target::name("ünï_cödé"), Target::id("0261d8287f4d6001")),
+            (Target::name(""),        Target::name("")),
+        ];
+
+        for (org, bucket) in cases.iter()
+        {
+            let path = meta(org.clone(), bucket.clone()).to_path();
+            let back = ArchiveMeta::from_path(&path).unwrap();
+
+            assert_eq!((&back.org, &back.bucket), (org, bucket));
+            assert_eq!((back.precision, back.rounding), (Precision::Milliseconds, Rounding::Floor));
+        }
+    }
+
+
+    #[test]
+    fn reads_joined_names()
+    {
+        let plain = ArchiveMeta::from_path(&joined_path("org_bucket_s")).unwrap();
+
+        assert_eq!((plain.org, plain.bucket), (Target::name("org"), Target::name("bucket")));
+        assert_eq!((plain.precision, plain.rounding), (Precision::Seconds, Rounding::default()));
+
+        assert!(ArchiveMeta::from_path(&joined_path("org_bucket")).is_err());
+        assert!(ArchiveMeta::from_path(&joined_path("org_bucket_ms_floor")).is_err());
+    }
+
+
+    #[test]
+    fn rejects_malformed_prefixed_names()
+    {
+        let names = ["3:org", "3:org6:bucket", "x:org", "9:org6:bucket2:ns5:trunc2:nn", "3:org6:bucket2:ns5:trunc2:xx"];
+
+        for name in names.iter()
+        {
+            let path = format!("{}{}.log", PREFIXED, encode(name));
+
+            assert!(ArchiveMeta::from_path(Path::new(&path)).is_err(), "{}", name);
+        }
+    }
+
+
+    #[test]
+    fn migrates_joined_names()
+    {
+        let dir = TempDir::new("migrate");
+
+        let line = json::to_string(&record("org", "bucket", 1).measurements[0]).unwrap();
+
+        std::fs::write(dir.0.join(joined_path("org_bucket_s")), format!("{}\n", line)).unwrap();
+
+        let mut backlog = FileBacklog::new(&dir.0).unwrap();
+
+        backlog.write_pending(&record("org", "bucket", 2)).unwrap();
+
+        let records = backlog.read_pending().unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].measurements().len(), 2);
+
+        assert!(! dir.0.join(joined_path("org_bucket_s")).exists());
+
+        backlog.truncate_pending(&records[0]).unwrap();
+
+        assert_eq!(std::fs::read_dir(&dir.0).unwrap().count(), 0);
+    }
+
+
+    #[test]
+    fn merges_joined_names_into_current_ones()
+    {
+        let dir = TempDir::new("merge");
+
+        let rec  = record("org", "bucket", 1);
+        let line = json::to_string(&rec.measurements[0]).unwrap();
+
+        std::fs::write(dir.0.join(joined_path("org_bucket_s")), format!("{}\n", line)).unwrap();
+        std::fs::write(dir.0.join(ArchiveMeta::from_record(&rec).to_path()), format!("{}\n{}\n", line, line)).unwrap();
+
+        let records = FileBacklog::new(&dir.0).unwrap().read_pending().unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].measurements().len(), 3);
+        assert_eq!(std::fs::read_dir(&dir.0).unwrap().count(), 1);
     }
 }
@@ -13,9 +13,11 @@ pub use file::FileBacklog;
 use crate::Record;
 use crate::InfluxResult;
 
+#[cfg(feature="async")] use async_trait::async_trait;
+
 
 /// API definition that any backlog service needs to abide by so the [Client](struct.Client.html) can use it.
-pub trait Backlog: Debug + Send + Sync
+pub trait Backlog: Debug + Send
 {
     /// Return any pending records that sits in backlog and requires to be commited.
     fn read_pending(&mut self) -> InfluxResult<Vec<Record>>;
@@ -29,6 +31,58 @@ pub trait Backlog: Debug + Send + Sync
 }
 
 
+impl<B: Backlog + ?Sized> Backlog for Box<B>
+{
+    fn read_pending(&mut self) -> InfluxResult<Vec<Record>> {
+        (**self).read_pending()
+    }
+
+    fn write_pending(&mut self, record: &Record) -> InfluxResult<()> {
+        (**self).write_pending(record)
+    }
+
+    fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()> {
+        (**self).truncate_pending(record)
+    }
+}
+
+
+/// Async counterpart of [Backlog](trait.Backlog.html), as used by the [AsyncClient](struct.AsyncClient.html). Every
+/// blocking backlog is one as well, though it blocks the executor while reading or writing.
+#[cfg(feature="async")]
+#[async_trait]
+pub trait AsyncBacklog: Debug + Send
+{
+    /// Return any pending records that sits in backlog and requires to be commited.
+    async fn read_pending(&mut self) -> InfluxResult<Vec<Record>>;
+
+    /// Write records that could not be commited, so they get written into backlog for future processing.
+    async fn write_pending(&mut self, record: &Record) -> InfluxResult<()>;
+
+    /// Empty backlog from pending records. This gets called once all pending records have been
+    /// successfully commited.
+    async fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()>;
+}
+
+
+#[cfg(feature="async")]
+#[async_trait]
+impl<B: Backlog> AsyncBacklog for B
+{
+    async fn read_pending(&mut self) -> InfluxResult<Vec<Record>> {
+        Backlog::read_pending(self)
+    }
+
+    async fn write_pending(&mut self, record: &Record) -> InfluxResult<()> {
+        Backlog::write_pending(self, record)
+    }
+
+    async fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()> {
+        Backlog::truncate_pending(self, record)
+    }
+}
+
+
 /// Backlog that does nothing
 #[derive(Debug)]
 pub struct NoopBacklog;
@@ -0,0 +1,344 @@
+//!
+//! Background Batching of Records
+//!
+use crate::Client;
+use crate::Record;
+use crate::Target;
+use crate::Rounding;
+use crate::Precision;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use std::collections::HashMap;
+
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use std::thread;
+use std::thread::JoinHandle;
+
+use std::time::Duration;
+use std::time::Instant;
+
+
+type BatchKey = (Target, Target, Precision, Rounding);
+
+
+enum Command
+{
+    Write(Record),
+    Flush(mpsc::Sender<InfluxResult<()>>),
+    Shutdown,
+}
+
+
+/// Writer that collects records in the background and writes them batched, instead of sending one request per
+/// [Record](struct.Record.html). Records sharing organization, bucket, precision and rounding get merged into a single
+/// batch, which is written once it reaches the given number of measurements, or once the flush interval passed.
+///
+/// Writing happens on a thread of its own through the given [Client](struct.Client.html), so failed batches end up in
+/// its backlog just like with [Client::write](struct.Client.html#method.write). They are otherwise only logged, unless
+/// flushed explicitly. Dropping the writer flushes whatever is left and waits for it to be written, senders fail from
+/// then on.
+///
+/// Note that measurements left to be stamped by the server get the time of the batch being written, not the one of
+/// their record being handed over.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use influxc::Client;
+/// use influxc::Record;
+/// use influxc::Credentials;
+/// use influxc::BatchWriter;
+///
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let creds  = Credentials::from_token("TOKEN");
+/// let client = Client::build("http://127.0.0.1:8086".into(), creds)
+///     .finish()
+///     .expect("Failed to build client");
+///
+/// let writer = BatchWriter::new(client, 5000, Duration::from_secs(1));
+///
+/// let workers = (0..4)
+///     .map(|idx| {
+///         let sender = writer.sender();
+///
+///         thread::spawn(move || {
+///             let mut rec = Record::new("org", "bucket");
+///
+///             rec.measurement("worker").tag("idx", &idx.to_string()).field("done", true);
+///
+///             sender.write(rec).expect("Writer is gone");
+///         })
+///     })
+///     .collect::<Vec<_>>();
+///
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+///
+/// writer.flush().expect("Failed to write batches");
+/// ```
+#[derive(Debug)]
+pub struct BatchWriter
+{
+    sender: BatchSender,
+    thread: Option<JoinHandle<()>>,
+}
+
+
+/// Handle to hand records over to a [BatchWriter](struct.BatchWriter.html) from other threads. Cheap to clone. Fails
+/// once the writer got dropped, as records handed over after would never be written.
+#[derive(Debug, Clone)]
+pub struct BatchSender
+{
+    channel: mpsc::Sender<Command>,
+
+    /// Set by the writer on shutdown, held while sending so nothing gets queued behind the shutdown command
+    stopped: Arc<RwLock<bool>>,
+}
+
+
+impl BatchWriter
+{
+    /// Start writing batches through the client, each holding at most the given number of measurements, flushing them
+    /// at least once every interval.
+    pub fn new(client: Client, max_lines: usize, interval: Duration) -> Self
+    {
+        let (channel, receiver) = mpsc::channel();
+
+        let worker = Worker {client, receiver, max_lines: max_lines.max(1), interval, batches: HashMap::new()};
+
+        let thread = thread::Builder::new()
+            .name("influxc-batch".to_owned())
+            .spawn(move || worker.run())
+            .expect("Failed to spawn batch writer thread");
+
+        let sender = BatchSender {channel, stopped: Arc::new(RwLock::new(false))};
+
+        Self {sender, thread: Some(thread)}
+    }
+
+    /// Handle to write records from other threads
+    pub fn sender(&self) -> BatchSender
+    {
+        self.sender.clone()
+    }
+
+    /// Hand the record over to be written with the next batch
+    pub fn write(&self, record: Record) -> InfluxResult<()>
+    {
+        self.sender.write(record)
+    }
+
+    /// Write all batches collected so far and wait for it, returning the first failure
+    pub fn flush(&self) -> InfluxResult<()>
+    {
+        self.sender.flush()
+    }
+}
+
+
+impl Drop for BatchWriter
+{
+    fn drop(&mut self)
+    {
+        {
+            let mut stopped = self.sender.stopped.write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+            *stopped = true;
+
+            if self.sender.channel.send(Command::Shutdown).is_err() {
+                return;
+            }
+        }
+
+        if let Some(thread) = self.thread.take()
+        {
+            if thread.join().is_err() {
+                error!("Batch writer thread panicked, batches left unwritten are lost");
+            }
+        }
+    }
+}
+
+
+impl BatchSender
+{
+    /// Hand the record over to be written with the next batch
+    pub fn write(&self, record: Record) -> InfluxResult<()>
+    {
+        self.send(Command::Write(record))
+    }
+
+    /// Write all batches collected so far and wait for it, returning the first failure
+    pub fn flush(&self) -> InfluxResult<()>
+    {
+        let (ack, result) = mpsc::channel();
+
+        self.send(Command::Flush(ack))?;
+
+        result.recv()
+            .map_err(|_| Self::closed())?
+    }
+
+    fn send(&self, command: Command) -> InfluxResult<()>
+    {
+        let stopped = self.stopped.read()
+            .map_err(|_| Self::closed())?;
+
+        if *stopped {
+            return Err(Self::closed());
+        }
+
+        self.channel.send(command)
+            .map_err(|_| Self::closed())
+    }
+
+    fn closed() -> InfluxError
+    {
+        "Batch writer is shut down".into()
+    }
+}
+
+
+/// State of the background thread
+struct Worker
+{
+    client:   Client,
+    receiver: mpsc::Receiver<Command>,
+
+    max_lines: usize,
+    interval:  Duration,
+
+    batches: HashMap<BatchKey, Record>,
+}
+
+
+impl Worker
+{
+    fn run(mut self)
+    {
+        let mut deadline = Instant::now() + self.interval;
+
+        loop
+        {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+
+            match self.receiver.recv_timeout(timeout)
+            {
+                Ok(Command::Write(record)) => { self.collect(record); }
+                Ok(Command::Flush(ack))    => { let _ = ack.send(self.flush()); }
+
+                Ok(Command::Shutdown) | Err(RecvTimeoutError::Disconnected) => {
+                    let _ = self.flush();
+                    break;
+                }
+
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+            // checked after every command, so a steady stream of records does not hold back the interval
+            if Instant::now() >= deadline
+            {
+                let _ = self.flush();
+                deadline = Instant::now() + self.interval;
+            }
+        }
+    }
+
+    fn collect(&mut self, record: Record)
+    {
+        let key = record.batch_key();
+
+        let batch = self.batches.entry(key.clone())
+            .or_insert_with(|| {
+                Record::targeting(record.org.clone(), record.bucket.clone())
+                    .precision(record.precision.clone())
+                    .rounding(record.rounding)
+            });
+
+        batch.absorb(record);
+
+        if batch.measurements.len() >= self.max_lines
+        {
+            if let Some(batch) = self.batches.remove(&key) {
+                let _ = self.write(&batch);
+            }
+        }
+    }
+
+    /// Write all batches, returning the first failure
+    fn flush(&mut self) -> InfluxResult<()>
+    {
+        let mut result = Ok(());
+
+        for (_, batch) in std::mem::take(&mut self.batches)
+        {
+            if let Err(e) = self.write(&batch)
+            {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn write(&self, batch: &Record) -> InfluxResult<()>
+    {
+        let result = self.client.write(batch);
+
+        if let Err(e) = &result {
+            error!("Failed to write batch of {} measurements: {}", batch.measurements.len(), e);
+        }
+
+        result
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Credentials;
+
+
+    fn writer() -> BatchWriter
+    {
+        let client = Client::build("http://127.0.0.1:8086".into(), Credentials::from_token("TOKEN"))
+            .finish()
+            .unwrap();
+
+        BatchWriter::new(client, 100, Duration::from_secs(3600))
+    }
+
+
+    #[test]
+    fn fails_after_shutdown()
+    {
+        let writer = writer();
+        let sender = writer.sender();
+
+        writer.flush().unwrap();
+
+        drop(writer);
+
+        let mut rec = Record::new("org", "bucket");
+        rec.measurement("cpu").field("load", 0.5);
+
+        assert!(sender.write(rec).is_err());
+        assert!(sender.flush().is_err());
+    }
+}
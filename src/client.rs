@@ -7,31 +7,74 @@ use crate::ClientBuilder;
 
 use crate::Backlog;
 
+use crate::writing;
+use crate::writing::WriteOptions;
+use crate::transport::Transport;
+
+use crate::ChunkError;
 use crate::InfluxError;
 use crate::InfluxResult;
 
-use crate::ApiDelayError;
-use crate::ApiGenericError;
-use crate::ApiOversizeError;
-use crate::ApiMalformationError;
-
-use crate::b64;
-
 use crate::ReqwUrl;
 use crate::ReqwClient;
 use crate::ReqwMethod;
-use crate::ReqwRequestBuilder;
+
+use std::time::Instant;
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::sync::MutexGuard;
 
 
 /// The basic unit of interactino with the InfluxDB API.
-#[derive(Debug)]
+///
+/// Clones share the connection pool, credentials, backlog and settings, so a single client can be handed to any
+/// number of threads writing concurrently. Backlogged records are taken out of the backlog by the clone replaying them,
+/// so each gets replayed only once. Records failing anew go back behind those backlogged meanwhile.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use influxc::Client;
+/// use influxc::Record;
+/// use influxc::Credentials;
+///
+/// use std::thread;
+///
+/// let client = Client::build("http://127.0.0.1:8086".into(), Credentials::from_token("TOKEN"))
+///     .finish()
+///     .expect("Failed to build client");
+///
+/// let workers = (0..4)
+///     .map(|idx| {
+///         let client = client.clone();
+///
+///         thread::spawn(move || {
+///             let mut rec = Record::new("org", "bucket");
+///
+///             rec.measurement("worker").tag("idx", &idx.to_string()).field("done", true);
+///
+///             client.write(&rec)
+///         })
+///     })
+///     .collect::<Vec<_>>();
+///
+/// for worker in workers {
+///     worker.join().unwrap().expect("Failed to write");
+/// }
+/// ```
+#[derive(Debug, Clone)]
 pub struct Client
 {
     url:    ReqwUrl,
-    creds:  Credentials,
+    creds:  Arc<RwLock<Credentials>>,
     client: ReqwClient,
 
-    backlog: Box<dyn Backlog>,
+    backlog: Arc<Mutex<Box<dyn Backlog>>>,
+
+    pub(crate) org:     Option<String>,
+    pub(crate) options: Arc<WriteOptions>,
 }
 
 
@@ -43,16 +86,17 @@ impl Client
         ClientBuilder::new(url, creds)
     }
 
-    /// Directly construct this [Client](struct.Client.html).
+    /// Directly construct this [Client](struct.Client.html), with default TLS and HTTP settings.
     pub fn new(url: String, creds: Credentials, backlog: Box<dyn Backlog>) -> InfluxResult<Self>
     {
-        let ignore_cert = std::env::var("INFLUX_UNSAFE_TLS").ok()
-            .unwrap_or_else(|| "false".to_owned())
-            .parse()?;
+        Self::assemble(url, creds, backlog, &Transport::default(), WriteOptions::default())
+    }
 
-        let client = ReqwClient::builder()
-            .danger_accept_invalid_certs(ignore_cert)
-            .build()?;
+    /// Construct the client as configured on the [ClientBuilder](struct.ClientBuilder.html)
+    pub(crate) fn assemble(url: String, creds: Credentials, backlog: Box<dyn Backlog>, transport: &Transport,
+        options: WriteOptions) -> InfluxResult<Self>
+    {
+        let client = transport.blocking()?;
 
         let url = match ReqwUrl::parse(&url)
         {
@@ -60,33 +104,64 @@ impl Client
             Err(e)  => { return Err(format!("Failed to parse URL: {} due to {}", url, e).into()) }
         };
 
-        let mut this = Self {client, url, creds, backlog};
+        let this = Self {
+            client, url,
+
+            creds:   Arc::new(RwLock::new(creds)),
+            backlog: Arc::new(Mutex::new(backlog)),
+            org:     None,
+            options: Arc::new(options),
+        };
 
         this.authenticate()?;
 
         Ok(this)
     }
 
+    /// Organization set on the [ClientBuilder](struct.ClientBuilder.html), or taken from the environment or influx CLI
+    /// config it was created from
+    pub fn org(&self) -> Option<&str>
+    {
+        self.org.as_deref()
+    }
+
     /// Submit a [Record](struct.Record.html) to be written to InfluxDB. Or backlogged if you set a backlogger.
-    pub fn write(&mut self, record: &Record) -> InfluxResult<()>
+    /// Records failing validation, compaction or field type checks, if enabled, are rejected right away and never
+    /// backlogged.
+    ///
+    /// Records exceeding the size limits set on the [ClientBuilder](struct.ClientBuilder.html), or rejected by the
+    /// server as too large, get written in chunks. Should only some of them fail, only those get backlogged and are
+    /// reported by [InfluxError::WritePartial](enum.InfluxError.html#variant.WritePartial). Single lines too large for
+    /// the server are never backlogged, as they would fail forever.
+    pub fn write(&self, record: &Record) -> InfluxResult<()>
     {
+        let record = self.options.prepare(record)?;
+
         if let Err(e) = self.write_backlog() {
-            self.backlog.write_pending(&record)?; Err(e)
+            self.write_pending(&record)?; Err(e)
         }
         else
         {
-            let result = self.write_record(&record);
+            let mut errors = Vec::new();
 
-            if result.is_err() {
-                self.backlog.write_pending(&record)?;
+            for (chunk, error) in self.write_chunks(&record)?
+            {
+                if ! matches!(error.error(), InfluxError::WriteOversized(_)) {
+                    self.write_pending(&chunk)?;
+                }
+
+                errors.push(error);
             }
 
-            result
+            writing::chunk_result(&record, errors)
         }
     }
 
     /// Submit pending/backlogged [Records](struct.Record.html) to writing. It will attempt to flush them to database.
-    pub fn flush(&mut self) -> InfluxResult<()>
+    ///
+    /// Chunks of a backlogged record that got written leave the backlog even if others fail, so they are not written
+    /// twice. Single lines too large for the server are dropped from it and reported in the error.
+    pub fn flush(&self) -> InfluxResult<()>
     {
         self.write_backlog()
     }
@@ -96,127 +171,244 @@ impl Client
 /// Private interface
 impl Client
 {
-    fn write_backlog(&mut self) -> InfluxResult<()>
+    /// Replay the backlog without holding on to it while writing, so clones keep backlogging meanwhile. The records
+    /// are taken out of it first, so no clone replays them as well, and what remains of them is put back on failure.
+    fn write_backlog(&self) -> InfluxResult<()>
     {
-        let records = self.backlog.read_pending()?;
+        let mut records = self.take_pending()?.into_iter();
 
-        for record in records.iter()
-        {
+        if records.len() > 0 {
             info!("Found {} backlogged entries, attempting to commit", records.len());
+        }
+
+        while let Some(record) = records.next()
+        {
+            let failed = match self.write_chunks(&record)
+            {
+                Ok(failed) => { failed }
+                Err(e)     => { self.restore_pending(std::iter::once(record).chain(records))?; return Err(e); }
+            };
 
-            if let Err(e) = self.write_record(&record) {
-                return Err(InfluxError::Error(format!("Unable to commit backlogged record: {}", e)));
+            let replay = writing::replay(&record, failed);
+
+            if let Err(e) = replay.result
+            {
+                let keep = if replay.rewrite { replay.keep } else { vec![record] };
+
+                self.restore_pending(keep.into_iter().chain(records))?;
+
+                return Err(e);
             }
-            else
+        }
+
+        Ok(())
+    }
+
+    /// Read the pending records and remove them from the backlog in one go
+    fn take_pending(&self) -> InfluxResult<Vec<Record>>
+    {
+        let mut backlog = self.backlog()?;
+
+        let records = backlog.read_pending()?;
+
+        for record in records.iter()
+        {
+            if let Err(e) = backlog.truncate_pending(record)
             {
-                let result = self.backlog.truncate_pending(&record);
+                let msg = format!("Failed to eliminate/truncate record from backlog: {}", e);
+                error!("{}", msg);
+                panic!("{}", msg);
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Put records taken from the backlog back into it, after any that got backlogged meanwhile
+    fn restore_pending<I: Iterator<Item=Record>>(&self, records: I) -> InfluxResult<()>
+    {
+        let mut backlog = self.backlog()?;
+
+        for record in records {
+            backlog.write_pending(&record)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_pending(&self, record: &Record) -> InfluxResult<()>
+    {
+        let record = self.options.pending(record);
+
+        self.backlog()?.write_pending(&record)
+    }
 
-                if let Err(e) = result
+    fn backlog(&self) -> InfluxResult<MutexGuard<'_, Box<dyn Backlog>>>
+    {
+        self.backlog.lock()
+            .map_err(|_| "Backlog poisoned by an earlier panic".into())
+    }
+
+    /// Write the record in chunks within the size limits, returning the failed ones
+    fn write_chunks(&self, record: &Record) -> InfluxResult<Vec<(Record, ChunkError)>>
+    {
+        let mut chunks = self.options.chunks(record)?;
+
+        while let Some((range, chunk)) = chunks.next()
+        {
+            let result = self.write_retrying(&chunk);
+
+            chunks.report(&self.options, range, chunk, result)?;
+        }
+
+        Ok(chunks.into_failed())
+    }
+
+    /// Write the record, retrying transient failures as the retry policy dictates
+    fn write_retrying(&self, record: &Record) -> InfluxResult<()>
+    {
+        let started      = Instant::now();
+        let mut attempts = 0;
+
+        loop
+        {
+            let result = self.write_record(record);
+
+            attempts += 1;
+
+            if let (Err(e), Some(policy)) = (&result, &self.options.retry)
+            {
+                if let Some(delay) = policy.next_delay(attempts, e, started.elapsed())
                 {
-                    let msg = format!("Failed to eliminate/truncate record from backlog: {}", e);
-                    error!("{}", msg);
-                    panic!("{}", msg);
-                }
-                else {
-                    return Ok(());
+                    warn!("Write attempt {} failed ({}), retrying in {:?}", attempts, e, delay);
+                    std::thread::sleep(delay);
+                    continue;
                 }
             }
-        }
 
-        Ok(())
+            return result;
+        }
     }
 
     fn write_record(&self, record: &Record) -> InfluxResult<()>
     {
         let mut url = self.url.clone();
 
-        url.set_path("/api/v2/write");
+        let request = {
+            let creds = self.creds.read()
+                .map_err(|_| InfluxError::from("Credentials poisoned by an earlier panic"))?;
 
-        let mut builder = self.client.request(ReqwMethod::POST, url);
+            self.options.write_request(record, &creds)?
+        };
 
-        builder = record.to_write_request(builder)?;
-        builder = self.inject_credentials(builder)?;
+        url.set_path(request.path);
 
-        debug!("Request: {:#?}", builder);
+        let (body, gzipped) = record.to_write_body(&self.options.default_tags, self.options.compression)?;
 
-        let reply = builder.send()?;
+        debug!("Request: POST {}", request);
 
-        match reply.status().as_u16()
-        {
-            204 => { info!("Written: {}", record); Ok(()) }
+        let mut builder = self.client.request(ReqwMethod::POST, url)
+            .header("Accept-Encoding", "gzip")
+            .query(&request.query);
 
-            400 => { Err(InfluxError::WriteMalformed(reply.json::<ApiMalformationError>()?)) }
-            401 => { Err(InfluxError::WriteUnauthorized(reply.json::<ApiGenericError>()?)) }
-            403 => { Err(InfluxError::WriteUnauthenticated(reply.json::<ApiGenericError>()?)) }
-            413 => { Err(InfluxError::WriteOversized(reply.json::<ApiOversizeError>()?)) }
-            429 => { Err(InfluxError::WriteOverquota(reply.json::<ApiDelayError>()?)) }
-            503 => { Err(InfluxError::WriteUnready(reply.json::<ApiDelayError>()?)) }
+        if let Some((header, value)) = request.auth {
+            builder = builder.header(header, value);
+        }
 
-            _   => { Err(InfluxError::WriteUnknown(reply.json::<ApiGenericError>()?)) }
+        if gzipped {
+            builder = builder.header("Content-Encoding", "gzip");
         }
+
+        builder = builder.body(body);
+
+        let reply   = builder.send()?;
+        let status  = reply.status().as_u16();
+        let headers = reply.headers().clone();
+        let body    = reply.bytes()?;
+
+        self.options.write_reply(status, &headers, &body)?;
+
+        info!("Written: {}", record);
+
+        Ok(())
     }
 
-    fn authenticate(&mut self) -> InfluxResult<()>
+    fn authenticate(&self) -> InfluxResult<()>
     {
-        if let Credentials::Basic{ref user, ref passwd, cookie: None} = self.creds
+        // 1.x has no sign in, but takes the credentials along with every request
+        if self.options.v1.is_some() {
+            return Ok(());
+        }
+
+        let mut creds = self.creds.write()
+            .map_err(|_| InfluxError::from("Credentials poisoned by an earlier panic"))?;
+
+        if let Some(auth) = creds.signin_header()
         {
             let mut url = self.url.clone();
 
             url.set_path("/api/v2/signin");
 
-            let b64creds = b64::encode(format!("{}:{}", user, passwd));
+            debug!("Request: POST {}", url.path());
 
             let req = self.client.request(ReqwMethod::POST, url)
-                .header("Authorization", format!("Basic {}", b64creds));
-
-            debug!("Request: {:#?}", req);
+                .header("Accept-Encoding", "gzip")
+                .header("Authorization", auth);
 
-            let rep = req.send()?;
+            let rep     = req.send()?;
+            let status  = rep.status().as_u16();
+            let headers = rep.headers().clone();
+            let body    = rep.bytes()?;
 
-            match rep.status().as_u16()
-            {
-                204 => {
-                    if let Some(cookie) = rep.headers().get("Set-Cookie")
-                    {
-                        let session = {
-                            if let Ok(s) = cookie.to_str() {
-                                s.to_owned()
-                            } else {
-                                return Err(format!("Failed to extract session cookie string: {:#?}", cookie).into());
-                            }
-                        };
-
-                        self.creds = Credentials::Basic {user: user.clone(), passwd: passwd.clone(), cookie: Some(session)};
-                    }
-                    else {
-                        return Err("Missing session cookie after successfull basic auth".into());
-                    }
-                }
-
-                401 => { return Err(InfluxError::AuthUnauthorized(rep.json::<ApiGenericError>()?)); }
-                403 => { return Err(InfluxError::AuthAccountDisabled(rep.json::<ApiGenericError>()?)); }
-                _   => { return Err(InfluxError::AuthUnknown(rep.json::<ApiGenericError>()?)); }
-            }
+            creds.signin_reply(status, &headers, &writing::decode_body(&headers, &body)?)?;
         }
 
         Ok(())
     }
+}
 
-    fn inject_credentials(&self, builder: ReqwRequestBuilder) -> InfluxResult<ReqwRequestBuilder>
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Compression;
+
+    use crate::stub::serve;
+    use crate::stub::MemoryBacklog;
+
+
+    fn record(load: f64) -> Record
     {
-        match &self.creds
-        {
-            Credentials::Basic{user: _, passwd: _, cookie: None} => {
-                Err("Missing session cookie from basic auth. This should not have happened!".into())
-            }
+        let mut rec = Record::new("org", "bucket").server_timestamps();
 
-            Credentials::Basic{user: _, passwd: _, cookie: Some(session)} => {
-                Ok(builder.header("Cookie", session))
-            }
+        rec.measurement("cpu").field("load", load);
 
-            Credentials::Token{token} => {
-                Ok(builder.header("Authorization", format!("Token {}", token)))
-            }
-        }
+        rec
+    }
+
+
+    #[test]
+    fn puts_back_what_failed_to_replay()
+    {
+        let (url, bodies) = serve(vec![204, 503]);
+
+        let mut backlog = MemoryBacklog::default();
+
+        backlog.write_pending(&record(0.5)).unwrap();
+        backlog.write_pending(&record(0.7)).unwrap();
+
+        let client = Client::build(url, Credentials::from_token("TOKEN"))
+            .compression(Compression::Off)
+            .backlog(backlog.clone())
+            .finish()
+            .unwrap();
+
+        assert!(matches!(client.flush(), Err(InfluxError::Annotated(..))));
+
+        assert_eq!(bodies.recv().unwrap(), "cpu load=0.5\n");
+        assert_eq!(bodies.recv().unwrap(), "cpu load=0.7\n");
+        assert_eq!(backlog.lines(), vec!["cpu load=0.7\n"]);
     }
 }
@@ -0,0 +1,50 @@
+//!
+//! Compression of Request Bodies
+//!
+
+
+/// Whether and how to compress the line protocol sent to InfluxDB. Defaults to gzip at level 6.
+///
+/// Small writes of a handful of points hardly shrink, but still pay for compressing them, which
+/// [GzipAbove](#variant.GzipAbove) avoids. Servers or proxies not accepting `Content-Encoding: gzip` need
+/// [Off](#variant.Off).
+///
+/// ## Examples
+///
+/// ```rust
+/// use influxc::Client;
+/// use influxc::Credentials;
+/// use influxc::Compression;
+///
+/// let client = Client::build("http://127.0.0.1:8086".into(), Credentials::from_token("TOKEN"))
+///     .compression(Compression::GzipAbove {bytes: 4096, level: 9})
+///     .finish()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression
+{
+    /// Send plain line protocol
+    Off,
+
+    /// Gzip every request body at the given level, from 0 (none) to 9 (best)
+    Gzip(u32),
+
+    /// Gzip request bodies larger than the given number of bytes at the given level, sending smaller ones plain
+    GzipAbove {
+        /// Size of the plain body above which it gets compressed
+        bytes: usize,
+
+        /// Level from 0 (none) to 9 (best)
+        level: u32,
+    },
+}
+
+
+impl Default for Compression
+{
+    fn default() -> Self
+    {
+        Compression::Gzip(6)
+    }
+}
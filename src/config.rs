@@ -0,0 +1,183 @@
+//!
+//! Connection Settings Shared with the influx CLI
+//!
+use crate::InfluxResult;
+use crate::InfluxErrorAnnotate;
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use std::collections::HashMap;
+
+
+/// Server, token and organization, as the influx CLI keeps them
+#[derive(Debug)]
+pub(crate) struct Profile
+{
+    pub(crate) url:   String,
+    pub(crate) token: String,
+    pub(crate) org:   Option<String>,
+}
+
+
+/// Profile as stored in the config file. Profiles other than the selected one may lack anything, they are of no
+/// concern to the client.
+#[derive(Debug, Deserialize)]
+struct StoredProfile
+{
+    #[serde(default)]
+    url: Option<String>,
+
+    #[serde(default)]
+    token: Option<String>,
+
+    #[serde(default)]
+    org: Option<String>,
+
+    #[serde(default)]
+    active: bool,
+}
+
+
+impl Profile
+{
+    /// Settings from the `INFLUX_HOST`, `INFLUX_TOKEN` and optional `INFLUX_ORG` environment variables
+    pub(crate) fn from_env() -> InfluxResult<Self>
+    {
+        Ok(Self {
+            url:   required_var("INFLUX_HOST")?,
+            token: required_var("INFLUX_TOKEN")?,
+            org:   std::env::var("INFLUX_ORG").ok().filter(|org| ! org.is_empty()),
+        })
+    }
+
+    /// Settings of the named profile of the config file, or of the active one
+    pub(crate) fn from_file(path: &Path, name: Option<&str>) -> InfluxResult<Self>
+    {
+        let text = std::fs::read_to_string(path)
+            .annotate(format!("While reading influx CLI configs: {:#?}", path))?;
+
+        Self::from_configs(&text, name, path)
+    }
+
+    /// Settings of the named profile of the configs read from the path, or of the active one, which has to have server
+    /// and token
+    fn from_configs(text: &str, name: Option<&str>, path: &Path) -> InfluxResult<Self>
+    {
+        let mut profiles = toml::from_str::<HashMap<String, StoredProfile>>(text)
+            .annotate(format!("While parsing influx CLI configs: {:#?}", path))?;
+
+        let name = match name
+        {
+            Some(name) => { name.to_owned() }
+
+            None => {
+                profiles.iter()
+                    .find(|(_, profile)| profile.active)
+                    .map(|(name, _)| name.clone())
+                    .ok_or_else(|| format!("No active profile in influx CLI configs: {:#?}", path))?
+            }
+        };
+
+        let profile = profiles.remove(&name)
+            .ok_or_else(|| format!("No profile '{}' in influx CLI configs: {:#?}", name, path))?;
+
+        match profile
+        {
+            StoredProfile{url: Some(url), token: Some(token), org, ..} => { Ok(Self {url, token, org}) }
+
+            StoredProfile{url, ..} => {
+                let missing = if url.is_none() { "url" } else { "token" };
+
+                Err(format!("Profile '{}' in influx CLI configs has no {}: {:#?}", name, missing, path).into())
+            }
+        }
+    }
+
+    /// Where the influx CLI keeps its configs, `~/.influxdbv2/configs` unless overridden by `INFLUX_CONFIGS_PATH`
+    pub(crate) fn default_path() -> InfluxResult<PathBuf>
+    {
+        if let Ok(path) = std::env::var("INFLUX_CONFIGS_PATH") {
+            return Ok(PathBuf::from(path));
+        }
+
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .ok_or("Could not determine home directory to find influx CLI configs in")?;
+
+        Ok(PathBuf::from(home).join(".influxdbv2").join("configs"))
+    }
+}
+
+
+fn required_var(name: &str) -> InfluxResult<String>
+{
+    match std::env::var(name)
+    {
+        Ok(value) if ! value.is_empty() => { Ok(value) }
+        _                               => { Err(format!("Environment variable {} is not set", name).into()) }
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const CONFIGS: &str = r#"
+        [default]
+          url = "http://127.0.0.1:8086"
+          token = "TOKEN"
+          org = "ops"
+          active = true
+
+        [staging]
+          url = "http://127.0.0.1:9086"
+          token = "STAGING-TOKEN"
+
+        [unfinished]
+          url = "http://127.0.0.1:7086"
+    "#;
+
+
+    fn select(text: &str, name: Option<&str>) -> InfluxResult<Profile>
+    {
+        Profile::from_configs(text, name, Path::new("configs"))
+    }
+
+
+    #[test]
+    fn selects_named_or_active_profile()
+    {
+        let active = select(CONFIGS, None).unwrap();
+
+        assert_eq!((active.url.as_str(), active.token.as_str()), ("http://127.0.0.1:8086", "TOKEN"));
+        assert_eq!(active.org.as_deref(), Some("ops"));
+
+        let staging = select(CONFIGS, Some("staging")).unwrap();
+
+        assert_eq!((staging.url.as_str(), staging.token.as_str()), ("http://127.0.0.1:9086", "STAGING-TOKEN"));
+        assert_eq!(staging.org, None);
+    }
+
+
+    #[test]
+    fn requires_completeness_of_selected_profile_only()
+    {
+        assert!(select(CONFIGS, Some("staging")).is_ok());
+
+        let error = select(CONFIGS, Some("unfinished")).unwrap_err().to_string();
+
+        assert!(error.contains("Profile 'unfinished' in influx CLI configs has no token"), "{}", error);
+    }
+
+
+    #[test]
+    fn fails_without_profile()
+    {
+        assert!(select(CONFIGS, Some("prod")).unwrap_err().to_string().contains("No profile 'prod'"));
+        assert!(select("[default]\nurl = 'x'\n", None).unwrap_err().to_string().contains("No active profile"));
+        assert!(select("[default\n", None).is_err());
+    }
+}
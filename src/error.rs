@@ -2,11 +2,16 @@
 //! Error Handling
 //!
 use crate::JsonError;
+use crate::TomlError;
 
 use crate::ReqwError;
 
 use crate::Deserialize;
 
+use crate::ValueType;
+
+use std::ops::Range;
+
 
 pub(crate) type InfluxResult<T> = Result<T, InfluxError>;
 
@@ -71,6 +76,9 @@ pub enum InfluxError
     /// Chaining of [JsonError](serde_json::error::Error)
     Json(JsonError),
 
+    /// Chaining of [TomlError](toml::de::Error)
+    Toml(TomlError),
+
     /// Chaining of [ReqwestError](reqwest::error::Error)
     Reqwest(ReqwError),
 
@@ -103,6 +111,19 @@ pub enum InfluxError
 
     /// Write API: InfluxDB server side error. Investigate.
     WriteUnknown(ApiGenericError),
+
+    /// Line Protocol: Syntax error while parsing. Points at the line and column of the offending input.
+    LineProtocol(LineProtocolError),
+
+    /// Validation: Record contains measurements InfluxDB would reject. Lists every offense found.
+    Invalid(Vec<ValidationError>),
+
+    /// Schema: Record writes fields with a different type than previously seen. Lists every conflict found.
+    FieldConflict(Vec<FieldConflictError>),
+
+    /// Write API: Record got split into chunks and some of them failed. Lists every failed chunk, all others got
+    /// written.
+    WritePartial(Vec<ChunkError>),
 }
 
 
@@ -114,9 +135,10 @@ pub struct ApiGenericError
 }
 
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct ApiDelayError
 {
+    #[serde(default)]
     delay: i64,
 }
 
@@ -144,6 +166,195 @@ pub struct ApiOversizeError
 }
 
 
+/// Syntax error found while parsing line protocol. Line and column are both 1-based, columns counting characters.
+#[derive(Debug)]
+pub struct LineProtocolError
+{
+    line:    usize,
+    column:  usize,
+    message: String,
+}
+
+
+impl LineProtocolError
+{
+    pub(crate) fn new(line: usize, column: usize, message: String) -> Self
+    {
+        Self {line, column, message}
+    }
+
+    /// Line number the error was found on
+    pub fn line(&self) -> usize
+    {
+        self.line
+    }
+
+    /// Column within the line the error was found at
+    pub fn column(&self) -> usize
+    {
+        self.column
+    }
+
+    /// Description of what went wrong
+    pub fn message(&self) -> &str
+    {
+        &self.message
+    }
+}
+
+
+/// Kind of offense a [ValidationError](struct.ValidationError.html) reports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationKind
+{
+    /// Measurement name is empty
+    EmptyName,
+
+    /// Tag key or field key is empty
+    EmptyKey,
+
+    /// Tag value is empty
+    EmptyTagValue,
+
+    /// Measurement has no fields, which line protocol does not allow
+    NoFields,
+
+    /// Field required by a [MeasurementBuilder](struct.MeasurementBuilder.html) is missing
+    MissingField,
+
+    /// Name or key is `time` or starts with an underscore, which InfluxDB reserves for itself (`_field`,
+    /// `_measurement`, ...)
+    ReservedKey,
+
+    /// Float field is NaN or infinite
+    NonFiniteFloat,
+
+    /// Name, key or tag value contains a newline, which line protocol is not able to escape
+    Newline,
+
+    /// Field got a different value by an earlier measurement of the same series and timestamp, found while compacting
+    /// the record
+    ConflictingField,
+}
+
+
+/// Offense found while validating a [Record](struct.Record.html), pointing at the measurement and key at fault.
+#[derive(Debug)]
+pub struct ValidationError
+{
+    index: usize,
+    key:   Option<String>,
+    kind:  ValidationKind,
+}
+
+
+impl ValidationError
+{
+    pub(crate) fn new(index: usize, key: Option<&str>, kind: ValidationKind) -> Self
+    {
+        Self {index, key: key.map(str::to_owned), kind}
+    }
+
+    /// Index of the offending measurement within the record
+    pub fn index(&self) -> usize
+    {
+        self.index
+    }
+
+    /// Offending tag or field key. Absent if the offense concerns the measurement as a whole
+    pub fn key(&self) -> Option<&str>
+    {
+        self.key.as_deref()
+    }
+
+    /// What is wrong with it
+    pub fn kind(&self) -> ValidationKind
+    {
+        self.kind
+    }
+}
+
+
+/// Field written with a different type than previously seen for the same measurement, as detected by the
+/// [SchemaTracker](struct.SchemaTracker.html).
+#[derive(Debug)]
+pub struct FieldConflictError
+{
+    index:    usize,
+    field:    String,
+    expected: ValueType,
+    found:    ValueType,
+}
+
+
+impl FieldConflictError
+{
+    pub(crate) fn new(index: usize, field: &str, expected: ValueType, found: ValueType) -> Self
+    {
+        Self {index, field: field.to_owned(), expected, found}
+    }
+
+    /// Index of the offending measurement within the record
+    pub fn index(&self) -> usize
+    {
+        self.index
+    }
+
+    /// Key of the offending field
+    pub fn field(&self) -> &str
+    {
+        &self.field
+    }
+
+    /// Type the field was previously written with
+    pub fn expected(&self) -> ValueType
+    {
+        self.expected
+    }
+
+    /// Type the field was attempted to be written with
+    pub fn found(&self) -> ValueType
+    {
+        self.found
+    }
+}
+
+
+/// Failure of a single chunk of a [Record](struct.Record.html) that got split to stay within the write size limits.
+#[derive(Debug)]
+pub struct ChunkError
+{
+    measurements: Range<usize>,
+    error:        InfluxError,
+}
+
+
+impl ChunkError
+{
+    pub(crate) fn new(measurements: Range<usize>, error: InfluxError) -> Self
+    {
+        Self {measurements, error}
+    }
+
+    /// Indices of the measurements within the record the chunk was made of
+    pub fn measurements(&self) -> Range<usize>
+    {
+        self.measurements.clone()
+    }
+
+    /// Why writing the chunk failed
+    pub fn error(&self) -> &InfluxError
+    {
+        &self.error
+    }
+
+    pub(crate) fn into_error(self) -> InfluxError
+    {
+        self.error
+    }
+}
+
+
 impl<T, E> InfluxErrorAnnotate<T> for Result<T, E>
     where E: Into<InfluxError> + std::error::Error
 {
@@ -163,6 +374,7 @@ impl From<std::io::Error>           for InfluxError { fn from(err: std::io::Erro
 impl From<std::str::ParseBoolError> for InfluxError { fn from(err: std::str::ParseBoolError) -> InfluxError { InfluxError::ParseBool(err) }}
 
 impl From<JsonError> for InfluxError { fn from(err: JsonError) -> InfluxError { InfluxError::Json(err) }}
+impl From<TomlError> for InfluxError { fn from(err: TomlError) -> InfluxError { InfluxError::Toml(err) }}
 impl From<ReqwError> for InfluxError { fn from(err: ReqwError) -> InfluxError { InfluxError::Reqwest(err) }}
 
 
@@ -179,6 +391,7 @@ impl std::fmt::Display for InfluxError
             Self::ParseBool(ref err) => { write!(f, "Parse Bool Error: {}",      err) }
 
             Self::Json(ref err)       => { write!(f, "Json Error: {}",    err) }
+            Self::Toml(ref err)       => { write!(f, "Toml Error: {}",    err) }
             Self::Reqwest(ref err)    => { write!(f, "Reqwest Error: {}", err) }
 
             Self::AuthUnauthorized(ref inner)     => { write!(f, "AuthUnauthorized({})",     inner) }
@@ -191,6 +404,34 @@ impl std::fmt::Display for InfluxError
             Self::WriteOverquota(ref inner)       => { write!(f, "WriteOverquota({})",       inner) }
             Self::WriteUnready(ref inner)         => { write!(f, "WriteUnready({})",         inner) }
             Self::WriteUnknown(ref inner)         => { write!(f, "WriteUnknown({})",         inner) }
+            Self::LineProtocol(ref inner)         => { write!(f, "LineProtocol({})",         inner) }
+
+            Self::Invalid(ref inner) => {
+                let offenses = inner.iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join("; ");
+
+                write!(f, "Invalid({})", offenses)
+            }
+
+            Self::FieldConflict(ref inner) => {
+                let conflicts = inner.iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join("; ");
+
+                write!(f, "FieldConflict({})", conflicts)
+            }
+
+            Self::WritePartial(ref inner) => {
+                let failures = inner.iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join("; ");
+
+                write!(f, "WritePartial({})", failures)
+            }
         }
     }
 }
@@ -238,6 +479,96 @@ impl std::fmt::Display for ApiOversizeError
 }
 
 
+impl ApiDelayError
+{
+    /// Time the server asks to wait before trying again
+    pub fn delay(&self) -> std::time::Duration
+    {
+        std::time::Duration::from_secs(self.delay.max(0) as u64)
+    }
+
+    /// Take the delay from the `Retry-After` header into account, whichever is longer
+    pub(crate) fn retry_after(&mut self, delay: i64)
+    {
+        self.delay = self.delay.max(delay);
+    }
+}
+
+
+impl ApiGenericError
+{
+    pub(crate) fn new(code: &str, message: String) -> Self
+    {
+        Self {code: code.to_owned(), message}
+    }
+}
+
+
+impl ApiMalformationError
+{
+    pub(crate) fn new(code: &str, message: String) -> Self
+    {
+        Self {code: code.to_owned(), err: message.clone(), line: None, message, op: "write".to_owned()}
+    }
+}
+
+
+impl ApiOversizeError
+{
+    /// Without the length known, which is all InfluxDB 1.x tells
+    pub(crate) fn new(message: String) -> Self
+    {
+        Self {code: "request too large".to_owned(), maxlen: 0, message}
+    }
+
+    /// Maximum request body length in bytes the server accepts, zero if it did not tell
+    pub fn maxlen(&self) -> i32
+    {
+        self.maxlen
+    }
+}
+
+
+impl std::fmt::Display for LineProtocolError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        write!(f, "line={}, column={}, message={}", self.line, self.column, self.message)
+    }
+}
+
+
+impl std::fmt::Display for ValidationError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self.key
+        {
+            Some(ref key) => { write!(f, "index={}, key={}, kind={:?}", self.index, key, self.kind) }
+            None          => { write!(f, "index={}, kind={:?}", self.index, self.kind) }
+        }
+    }
+}
+
+
+impl std::fmt::Display for FieldConflictError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        write!(f, "index={}, field={}, expected={:?}, found={:?}", self.index, self.field, self.expected, self.found)
+    }
+}
+
+
+impl std::fmt::Display for ChunkError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        write!(f, "measurements={}..{}, error={}", self.measurements.start, self.measurements.end, self.error)
+    }
+}
+
+
 impl std::error::Error for InfluxError
 {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
@@ -251,6 +582,7 @@ impl std::error::Error for InfluxError
             InfluxError::ParseBool(ref err) => { Some(err) }
 
             InfluxError::Json(ref err)    => { Some(err) }
+            InfluxError::Toml(ref err)    => { Some(err) }
             InfluxError::Reqwest(ref err) => { Some(err) }
 
             InfluxError::AuthUnauthorized(_)     => { None }
@@ -263,6 +595,10 @@ impl std::error::Error for InfluxError
             InfluxError::WriteOverquota(_)       => { None }
             InfluxError::WriteUnready(_)         => { None }
             InfluxError::WriteUnknown(_)         => { None }
+            InfluxError::LineProtocol(_)         => { None }
+            InfluxError::Invalid(_)              => { None }
+            InfluxError::FieldConflict(_)        => { None }
+            InfluxError::WritePartial(_)         => { None }
         }
     }
 }
@@ -5,24 +5,31 @@
 
 ### What this crate provides
 
-- Support for InfluxDB 2.x.
+- Support for InfluxDB 2.x, and for writing to InfluxDB 1.x through `ClientBuilder::v1`.
 - Backlog storage of Record's on failure to commit due to connectivity or configuration issues.
-- Build-in compression of requests.
+- Build-in compression of requests, configurable or off.
+- TLS and HTTP settings on `ClientBuilder`: custom CAs, client certificates, proxies and timeouts. Invalid certificates
+  are only accepted through `danger_accept_invalid_certs`, or the deprecated `INFLUX_UNSAFE_TLS=true` environment
+  variable.
+- Background batching of records from many threads through `BatchWriter`.
+- Mapping of structs to measurements through `#[derive(InfluxMeasurement)]` with the `derive` feature, or of any
+  `serde::Serialize` value through `Record::push_serialized`.
+- Timestamps from `time::OffsetDateTime` with the `time` feature, next to chrono and `std::time::SystemTime`.
+- `AsyncClient` for Tokio and other async runtimes with the `async` feature.
 
 ### What it does not provide
 
-- Support for InfluxDB 1.x
+- Support for InfluxDB 1.x beyond writing
 
 ### What is on the roadmap
 
-- Support for async/await as a feature. [#3](https://github.com/voipir/rust-influxc/issues/3)
 - Reduction of dependencies by switching the underlying reqwest library with hyper. [#4](https://github.com/voipir/rust-influxc/issues/4)
 - Support for sending, processing responses to queries. [#5](https://github.com/voipir/rust-influxc/issues/5)
 - Support for mapping native types to query response data like sqlx. [#6](https://github.com/voipir/rust-influxc/issues/6)
 
 ## Basic Usage
 
-```rust
+```rust,no_run
 use influxc::Client;
 use influxc::FileBacklog;
 
@@ -39,7 +46,7 @@ fn main() -> Result<(), InfluxError>
     let creds   = Credentials::from_basic("testuser", "testpasswd");
     let backlog = FileBacklog::new("./ignore/backlog")?;
 
-    let mut client = Client::build("http://127.0.0.1:8086".into(), creds)
+    let client = Client::build("http://127.0.0.1:8086".into(), creds)
         .backlog(backlog)
         .finish()?;
 
@@ -82,35 +89,61 @@ fn main() -> Result<(), InfluxError>
 use serde::Deserialize;
 
 use flate2::GzBuilder   as FlateGzipBuilder;
+use flate2::read::GzDecoder as FlateGzipDecoder;
 use flate2::Compression as FlateLevel;
 
 use serde_json as json;
 use serde_json::error::Error as JsonError;
 
+use toml::de::Error as TomlError;
+
 use base32 as b32;
 use base64 as b64;
 
 use reqwest::Url    as ReqwUrl;
 use reqwest::Error  as ReqwError;
 use reqwest::Method as ReqwMethod;
+use reqwest::Proxy  as ReqwProxy;
+
+use reqwest::Identity    as ReqwIdentity;
+use reqwest::Certificate as ReqwCertificate;
+
+use reqwest::header::HeaderMap as ReqwHeaderMap;
 
-use reqwest::blocking::Client         as ReqwClient;
-use reqwest::blocking::RequestBuilder as ReqwRequestBuilder;
+use reqwest::blocking::Client as ReqwClient;
+
+#[cfg(feature="async")] use reqwest::Client as ReqwAsyncClient;
 
 type Utc      = chrono::Utc;
 type DateTime = chrono::DateTime<chrono::Utc>;
 
 // Internals/Exports
 mod auth;
+mod batch;
+mod line;
 mod error;
 mod value;
+mod v1;
 mod client;
+mod config;
+mod compression;
+mod writing;
+mod transport;
 mod record;
+mod target;
+mod retry;
+mod schema;
+mod serialize;
 mod builder;
 mod precision;
+mod timestamp;
 mod backlogging;
 mod measurement;
 
+#[cfg(test)] mod stub;
+
+#[cfg(feature="async")] mod async_client;
+
 use error::InfluxResult;
 use error::InfluxErrorAnnotate;
 
@@ -122,19 +155,54 @@ use error::ApiMalformationError;
 pub use auth::Credentials;
 
 pub use error::InfluxError;
+pub use error::LineProtocolError;
+pub use error::ValidationKind;
+pub use error::ValidationError;
+pub use error::FieldConflictError;
+pub use error::ChunkError;
 
 pub use value::Value;
+pub use value::ValueType;
+pub use value::IntoFieldValue;
 
 pub use client::Client;
 
+pub use v1::V1Auth;
+
+pub use compression::Compression;
+
+pub use batch::BatchWriter;
+pub use batch::BatchSender;
+
+#[cfg(feature="async")] pub use async_client::AsyncClient;
+
 pub use record::Record;
 
+pub use target::Target;
+
+pub use retry::RetryPolicy;
+
+pub use schema::SchemaTracker;
+pub use schema::ConflictPolicy;
+
+pub use serialize::SerdeMapping;
+
 pub use builder::ClientBuilder;
 
 pub use precision::Precision;
+pub use precision::Rounding;
+
+pub use timestamp::IntoTimestamp;
 
 pub use backlogging::Backlog;
 pub use backlogging::FileBacklog;
 pub use backlogging::NoopBacklog;
 
+#[cfg(feature="async")] pub use backlogging::AsyncBacklog;
+
 pub use measurement::Measurement;
+pub use measurement::MeasurementBuilder;
+pub use measurement::IntoMeasurement;
+
+#[cfg(feature="derive")]
+pub use influxc_derive::InfluxMeasurement;
@@ -1,80 +1,480 @@
 //!
-//! Snippet Archive for Line Protocol Formatting/Parsing
+//! Line Protocol Formatting/Parsing
 //!
+//! Escaping rules follow the InfluxDB 2.x line protocol reference. Backslashes are always doubled, which the protocol
+//! interprets as a single literal backslash, so that a trailing backslash can never escape the following delimiter.
+//!
+use crate::Value;
+use crate::Precision;
+use crate::Measurement;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+use crate::LineProtocolError;
+
+use std::io;
+use std::io::Write;
+
+
+const MEASUREMENT_ESCAPES: &[char] = &[',',      ' ', '\\'];
+const KEY_ESCAPES:         &[char] = &[',', '=', ' ', '\\'];
+const STRING_ESCAPES:      &[char] = &['"',           '\\'];
+
+
+/// Write a measurement name, escaping commas, spaces and backslashes.
+pub(crate) fn write_measurement<W: Write>(writer: &mut W, name: &str) -> io::Result<()>
+{
+    write_escaped(writer, name, MEASUREMENT_ESCAPES)
+}
+
+
+/// Write a tag key, tag value or field key, escaping commas, equal signs, spaces and backslashes.
+pub(crate) fn write_key<W: Write>(writer: &mut W, key: &str) -> io::Result<()>
+{
+    write_escaped(writer, key, KEY_ESCAPES)
+}
+
+
+/// Write a field value in its line protocol representation. Strings get quoted and integers suffixed.
+pub(crate) fn write_value<W: Write>(writer: &mut W, value: &Value) -> io::Result<()>
+{
+    match value
+    {
+        Value::Integer(v)  => { write!(writer, "{}i", v) }
+        Value::Unsigned(v) => { write!(writer, "{}u", v) }
+        Value::Float(v)    => { write!(writer, "{}", v) }
+        Value::Boolean(v)  => { writer.write_all(if *v { b"true" } else { b"false" }) }
+
+        Value::String(v) => {
+            writer.write_all(b"\"")?;
+            write_escaped(writer, v, STRING_ESCAPES)?;
+            writer.write_all(b"\"")
+        }
+    }
+}
+
+
+/// Write the string in runs between special characters. All of them are ASCII, so scanning the UTF-8 bytes is safe.
+fn write_escaped<W: Write>(writer: &mut W, s: &str, special: &[char]) -> io::Result<()>
+{
+    let bytes = s.as_bytes();
+    let mut start = 0;
+
+    for (idx, byte) in bytes.iter().enumerate()
+    {
+        if byte.is_ascii() && special.contains(&char::from(*byte))
+        {
+            writer.write_all(&bytes[start..idx])?;
+            writer.write_all(&[b'\\', *byte])?;
+
+            start = idx + 1;
+        }
+    }
+
+    writer.write_all(&bytes[start..])
+}
+
+
+/// Parse a single line of line protocol. Empty lines and comments yield `None`. The line number is only used to
+/// annotate syntax errors. Lines without timestamp are left for the server to stamp.
+pub(crate) fn parse_line(text: &str, lineno: usize, precision: &Precision) -> InfluxResult<Option<Measurement>>
+{
+    let mut cur = Cursor::new(text, lineno);
+
+    cur.skip_whitespace();
+
+    if cur.is_eof() || cur.peek() == Some('#') {
+        return Ok(None);
+    }
+
+    let name = cur.token(&[',', ' '], MEASUREMENT_ESCAPES);
+
+    if name.is_empty() {
+        return Err(cur.error("Missing measurement name"));
+    }
+
+    let mut msrmt = Measurement::new(&name, None);
+
+    while cur.eat(',')
+    {
+        let key = cur.token(&['=', ',', ' '], KEY_ESCAPES);
+
+        if key.is_empty() {
+            return Err(cur.error("Missing tag key"));
+        }
+
+        if ! cur.eat('=') {
+            return Err(cur.error(format!("Expected '=' after tag key '{}'", key)));
+        }
+
+        let value = cur.token(&[',', ' '], KEY_ESCAPES);
+
+        if value.is_empty() {
+            return Err(cur.error(format!("Missing value for tag '{}'", key)));
+        }
+
+        msrmt.tags.insert(key, value);
+    }
+
+    if ! cur.eat(' ') {
+        return Err(cur.error("Expected space before field set"));
+    }
+
+    loop
+    {
+        let key = cur.token(&['=', ',', ' '], KEY_ESCAPES);
+
+        if key.is_empty() {
+            return Err(cur.error("Missing field key"));
+        }
+
+        if ! cur.eat('=') {
+            return Err(cur.error(format!("Expected '=' after field key '{}'", key)));
+        }
+
+        let value = cur.field_value()?;
+
+        msrmt.fields.insert(key, value);
+
+        if ! cur.eat(',') {
+            break;
+        }
+    }
+
+    cur.skip_whitespace();
+
+    if ! cur.is_eof()
+    {
+        let start = cur.pos;
+        let token = cur.take_while(|c| c != ' ' && c != '\t');
+
+        let value = token.parse::<i64>()
+            .map_err(|_| cur.error_at(start, format!("Invalid timestamp '{}'", token)))?;
+
+        let timestamp = precision.to_datetime(value)
+            .map_err(|e| cur.error_at(start, e))?;
+
+        msrmt.timestamp = Some(timestamp);
+
+        cur.skip_whitespace();
+
+        if ! cur.is_eof() {
+            return Err(cur.error("Unexpected characters after timestamp"));
+        }
+    }
+
+    Ok(Some(msrmt))
+}
+
+
+
+fn parse_scalar(token: &str) -> Option<Value>
+{
+    match token
+    {
+        "t" | "T" | "true"  | "True"  | "TRUE"  => Some(Value::Boolean(true)),
+        "f" | "F" | "false" | "False" | "FALSE" => Some(Value::Boolean(false)),
+
+        _ if token.ends_with('i') => {
+            token[..token.len() - 1].parse::<i64>().ok()
+                .map(Value::Integer)
+        }
+
+        _ if token.ends_with('u') => {
+            token[..token.len() - 1].parse::<u64>().ok()
+                .map(Value::Unsigned)
+        }
+
+        _ if is_float(token) => {
+            token.parse::<f64>().ok()
+                .map(Value::Float)
+        }
+
+        _ => None
+    }
+}
+
+
+/// Rust accepts `inf` and `NaN` as floats, line protocol does not. Only let plain decimal notation through.
+fn is_float(token: &str) -> bool
+{
+    token.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.')
+        && token.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+}
+
+
+/// Position tracking reader over a single line
+struct Cursor<'l>
+{
+    text:   &'l str,
+    pos:    usize,
+    lineno: usize,
+}
+
+
+impl<'l> Cursor<'l>
+{
+    fn new(text: &'l str, lineno: usize) -> Self
+    {
+        Self {text, pos: 0, lineno}
+    }
+
+    fn is_eof(&self) -> bool
+    {
+        self.pos >= self.text.len()
+    }
+
+    fn peek(&self) -> Option<char>
+    {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char>
+    {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool
+    {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8(); true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self)
+    {
+        self.take_while(|c| c == ' ' || c == '\t');
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, pred: F) -> &'l str
+    {
+        let start = self.pos;
+
+        while let Some(c) = self.peek()
+        {
+            if ! pred(c) {
+                break;
+            }
+
+            self.pos += c.len_utf8();
+        }
+
+        &self.text[start..self.pos]
+    }
+
+    /// Read up to the first unescaped stop character, resolving escape sequences. A backslash that does not precede
+    /// one of the escapable characters is taken literally.
+    fn token(&mut self, stops: &[char], escapes: &[char]) -> String
+    {
+        let mut token = String::new();
+
+        while let Some(c) = self.peek()
+        {
+            if stops.contains(&c) {
+                break;
+            }
+
+            self.bump();
+
+            if c == '\\'
+            {
+                match self.peek()
+                {
+                    Some(next) if escapes.contains(&next) => { token.push(next); self.bump(); }
+                    _                                     => { token.push(c); }
+                }
+            }
+            else {
+                token.push(c);
+            }
+        }
+
+        token
+    }
+
+    fn field_value(&mut self) -> InfluxResult<Value>
+    {
+        let start = self.pos;
+
+        if self.eat('"')
+        {
+            let mut value = String::new();
+
+            loop
+            {
+                match self.bump()
+                {
+                    None       => { return Err(self.error_at(start, "Unterminated string field value")); }
+                    Some('"')  => { break; }
+
+                    Some('\\') => {
+                        match self.peek()
+                        {
+                            Some(next) if next == '"' || next == '\\' => { value.push(next); self.bump(); }
+                            _                                         => { value.push('\\'); }
+                        }
+                    }
+
+                    Some(c) => { value.push(c); }
+                }
+            }
+
+            Ok(Value::String(value))
+        }
+        else
+        {
+            let token = self.take_while(|c| c != ',' && c != ' ');
+
+            parse_scalar(token)
+                .ok_or_else(|| self.error_at(start, format!("Invalid field value '{}'", token)))
+        }
+    }
+
+    fn error<M: ToString>(&self, msg: M) -> InfluxError
+    {
+        self.error_at(self.pos, msg)
+    }
+
+    fn error_at<M: ToString>(&self, pos: usize, msg: M) -> InfluxError
+    {
+        let column = self.text[..pos].chars().count() + 1;
+
+        InfluxError::LineProtocol(LineProtocolError::new(self.lineno, column, msg.to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Rounding;
+
+    use chrono::TimeZone;
+
+
+    /// Measurement name, tags, fields and the line the reference gives for them, without timestamp
+    type Case = (&'static str, &'static [(&'static str, &'static str)], Vec<(&'static str, Value)>, &'static str);
+
+
+    fn string(value: &str) -> Value
+    {
+        Value::String(value.to_owned())
+    }
+
 
+    /// Examples of the line protocol reference, along with the escaping rules it states for every element
+    fn corpus() -> Vec<Case>
+    {
+        vec![
+            // special characters in measurement names
+            ("my Measurement",  &[], vec![("fieldKey", string("string value"))], r#"my\ Measurement fieldKey="string value""#),
+            ("my,Measurement",  &[], vec![("fieldKey", Value::Integer(1))],      r#"my\,Measurement fieldKey=1i"#),
+            ("my=Measurement",  &[], vec![("fieldKey", Value::Integer(1))],      r#"my=Measurement fieldKey=1i"#),
+            (r"my\Measurement", &[], vec![("fieldKey", Value::Integer(1))],      r#"my\\Measurement fieldKey=1i"#),
 
-// pub fn from_line(line: &str) -> InfluxResult<Self>
-// {
-//     let parts = line.split(" ")
-//         .collect::<Vec<&str>>();
+            // special characters in tag keys and values
+            ("myMeasurement", &[("tag Key1", "tag Value1"), ("tag Key2", "tag Value2")], vec![("fieldKey", Value::Float(100.0))],
+                r#"myMeasurement,tag\ Key1=tag\ Value1,tag\ Key2=tag\ Value2 fieldKey=100"#),
+            ("myMeasurement", &[("tag,Key", "tag,Value")], vec![("fieldKey", Value::Integer(1))],
+                r#"myMeasurement,tag\,Key=tag\,Value fieldKey=1i"#),
+            ("myMeasurement", &[("tag=Key", "tag=Value")], vec![("fieldKey", Value::Integer(1))],
+                r#"myMeasurement,tag\=Key=tag\=Value fieldKey=1i"#),
+            ("myMeasurement", &[("tagKey", r"C:\dir\")], vec![("fieldKey", Value::Integer(1))],
+                r#"myMeasurement,tagKey=C:\\dir\\ fieldKey=1i"#),
+            ("myMeasurement", &[("tagKey", "🍭")], vec![("fieldKey", string("Launch 🚀"))],
+                r#"myMeasurement,tagKey=🍭 fieldKey="Launch 🚀""#),
 
-//     if parts.len() != 3 {
-//         return Err(format!("Invalid measurement line: '{}'. Please consult the InfluxDB line protocol documentation", line).into())
-//     }
+            // special characters in field keys
+            ("myMeasurement", &[], vec![("field Key", Value::Integer(1))], r#"myMeasurement field\ Key=1i"#),
+            ("myMeasurement", &[], vec![("field,Key", Value::Integer(1))], r#"myMeasurement field\,Key=1i"#),
+            ("myMeasurement", &[], vec![("field=Key", Value::Integer(1))], r#"myMeasurement field\=Key=1i"#),
 
-//     let part_msrmt  = parse_measurement(parts[0])?;
-//     let part_fields = parse_fields(parts[1])?;
-//     let part_tstamp = parts[2];
+            // quoted string fields, where only double quotes and backslashes get escaped
+            ("myMeasurement", &[], vec![("fieldKey", string(r#""string" within a string"#))],
+                r#"myMeasurement fieldKey="\"string\" within a string""#),
+            ("myMeasurement", &[], vec![("fieldKey", string("a, b = c"))], r#"myMeasurement fieldKey="a, b = c""#),
+            ("myMeasurement", &[], vec![("fieldKey", string(r"\"))],       r#"myMeasurement fieldKey="\\""#),
+            ("myMeasurement", &[], vec![("fieldKey", string(r"\\"))],      r#"myMeasurement fieldKey="\\\\""#),
+            ("myMeasurement", &[], vec![("fieldKey", string(r"\\\"))],     r#"myMeasurement fieldKey="\\\\\\""#),
+            ("myMeasurement", &[], vec![("fieldKey", string(""))],         r#"myMeasurement fieldKey="""#),
 
-//     let mut this = Self::new(part_msrmt.0);
+            // field value types, integers suffixed with i and unsigned ones with u
+            ("myMeasurement", &[], vec![("fieldKey", Value::Float(1.0))],          "myMeasurement fieldKey=1"),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Float(-1.5))],         "myMeasurement fieldKey=-1.5"),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Integer(1))],          "myMeasurement fieldKey=1i"),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Integer(12485903))],   "myMeasurement fieldKey=12485903i"),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Integer(-12485903))],  "myMeasurement fieldKey=-12485903i"),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Integer(i64::MIN))],   "myMeasurement fieldKey=-9223372036854775808i"),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Unsigned(1))],         "myMeasurement fieldKey=1u"),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Unsigned(u64::MAX))],  "myMeasurement fieldKey=18446744073709551615u"),
+            ("myMeasurement", &[], vec![("fieldKey", string("this is a string"))], r#"myMeasurement fieldKey="this is a string""#),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Boolean(true))],       "myMeasurement fieldKey=true"),
+            ("myMeasurement", &[], vec![("fieldKey", Value::Boolean(false))],      "myMeasurement fieldKey=false"),
 
-//     this.tags      = part_msrmt.1;
-//     this.fields    = part_fields;
-//     this.timestamp = Some(part_tstamp);
+            // several tags and fields, written sorted by key
+            ("home", &[("room", "Living Room")], vec![("temp", Value::Float(21.1)), ("hum", Value::Float(35.9)), ("co", Value::Integer(0))],
+                r#"home,room=Living\ Room co=0i,hum=35.9,temp=21.1"#),
+        ]
+    }
 
-//     Ok(this)
-// }
 
+    fn measurement(name: &str, tags: &[(&str, &str)], fields: Vec<(&str, Value)>, stamped: bool) -> Measurement
+    {
+        let timestamp = if stamped { Some(chrono::Utc.timestamp_opt(1556813561, 98000000).unwrap()) } else { None };
 
-// fn parse_measurement<'m>(fragment: &'m str) -> InfluxResult<(&'m str, BTreeMap<String, String>)>
-// {
-//     let mut tags  = BTreeMap::new();
-//     let mut parts = fragment.split(",");
+        let mut msrmt = Measurement::new(name, timestamp);
 
-//     if let Some(msrmt) = parts.next()
-//     {
-//         while let Some(tag) = parts.next()
-//         {
-//             let tag_parts = tag.split("=")
-//                 .collect::<Vec<&str>>();
+        for (key, value) in tags {
+            msrmt.tags.insert((*key).to_owned(), (*value).to_owned());
+        }
 
-//             if tag_parts.len() != 2 {
-//                 return Err("All tags must have a key=value format".into());
-//             }
+        for (key, value) in fields {
+            msrmt.fields.insert(key.to_owned(), value);
+        }
 
-//             let key   = tag_parts[0].to_owned();
-//             let value = tag_parts[1].to_owned();
+        msrmt
+    }
 
-//             tags.insert(key, value);
-//         }
 
-//         Ok((msrmt, tags))
-//     }
-//     else {
-//         Err("Measurement is missing in line".into())
-//     }
-// }
+    #[test]
+    fn encodes_reference_examples()
+    {
+        for (name, tags, fields, expected) in corpus()
+        {
+            let msrmt = measurement(name, tags, fields.clone(), true);
 
+            assert_eq!(msrmt.to_line(&Precision::Nanoseconds).unwrap(), format!("{} 1556813561098000000", expected));
+            assert_eq!(msrmt.to_line_rounded(&Precision::Seconds, Rounding::Truncate).unwrap(), format!("{} 1556813561", expected));
 
-// fn parse_fields(fragment: &str) -> InfluxResult<BTreeMap<String, String>>
-// {
-//     let mut fields = BTreeMap::new();
-//     let mut parts  = fragment.split(",");
+            let unstamped = measurement(name, tags, fields, false);
 
-//     while let Some(field) = parts.next()
-//     {
-//         let field_parts = field.split("=")
-//             .collect::<Vec<&str>>();
+            assert_eq!(unstamped.to_line(&Precision::Nanoseconds).unwrap(), expected);
+        }
+    }
 
-//         if field_parts.len() != 2 {
-//             return Err("All fields must have a key=value format".into());
-//         }
 
-//         let key   = field_parts[0].to_owned();
-//         let value = field_parts[1].to_owned();
+    /// Parsed back, the lines encode the same again
+    #[test]
+    fn parses_reference_examples()
+    {
+        for (name, tags, fields, expected) in corpus()
+        {
+            let line   = format!("{} 1556813561098", expected);
+            let parsed = parse_line(&line, 1, &Precision::Milliseconds).unwrap().unwrap();
 
-//         fields.insert(key, value);
-//     }
+            let msrmt = measurement(name, tags, fields, true);
 
-//     Ok(fields)
-// }
+            assert_eq!(parsed.name, msrmt.name, "{}", line);
+            assert_eq!(parsed.tags, msrmt.tags, "{}", line);
+            assert_eq!(parsed.timestamp, msrmt.timestamp, "{}", line);
+            assert_eq!(parsed.to_line(&Precision::Milliseconds).unwrap(), msrmt.to_line(&Precision::Milliseconds).unwrap(),
+                "{}", line);
+        }
+    }
+}
@@ -1,32 +1,48 @@
 //!
 //! Measurement to be Stored
 //!
+use crate::line;
 use crate::Value;
+use crate::IntoFieldValue;
+use crate::Record;
 use crate::Precision;
+use crate::Rounding;
+use crate::IntoTimestamp;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+use crate::ValidationKind;
+use crate::ValidationError;
 
 use crate::Utc;
 use crate::DateTime;
 
+use std::io::Write;
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+
 use std::collections::BTreeMap;
+use std::collections::btree_map;
 
 
 /// The smallest unit of recording. Multiple of these Measurements are fit in a [Record](struct.Record.html), which in
 /// turn is submitted to InfluxDB.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Measurement
 {
     pub(crate) name: String,
 
-    pub(crate) tags:   BTreeMap<String, String>,
+    pub(crate) tags:   Tags,
     pub(crate) fields: BTreeMap<String, Value>,
 
-    pub(crate) timestamp: DateTime,
+    pub(crate) timestamp: Option<DateTime>,
 }
 
 
 impl Measurement
 {
-    pub(crate) fn new(name: &str) -> Self
+    pub(crate) fn new(name: &str, timestamp: Option<DateTime>) -> Self
     {
         Self {
             name: name.to_owned(),
@@ -34,15 +50,70 @@ impl Measurement
             tags:   BTreeMap::new(),
             fields: BTreeMap::new(),
 
-            // TODO stamp in Drop of a MeasurementBuilder taking reference to collection in Record
-            timestamp: Utc::now(),
+            timestamp,
         }
     }
 
-    /// Set datetime of this Measurement
-    pub fn timestamp(&mut self, timestamp: DateTime) -> &mut Self
+    /// Parse a single line of line protocol. The timestamp, if present, is interpreted in units of the given precision.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Precision;
+    /// use influxc::Measurement;
+    /// use influxc::InfluxError;
+    ///
+    /// let line  = r#"home,room=Living\ Room temp=21.1,co=0i,on=t,label="a \"b\"" 1641024000"#;
+    /// let msrmt = Measurement::from_line(line, &Precision::Seconds).unwrap();
+    ///
+    /// assert_eq!(msrmt.to_line(&Precision::Seconds).unwrap(), r#"home,room=Living\ Room co=0i,label="a \"b\"",on=true,temp=21.1 1641024000"#);
+    ///
+    /// match Measurement::from_line("home,room=kitchen temp=hot", &Precision::Seconds) {
+    ///     Err(InfluxError::LineProtocol(e)) => { assert_eq!((e.line(), e.column()), (1, 24)); }
+    ///     _                                 => { panic!("expected syntax error"); }
+    /// }
+    /// ```
+    pub fn from_line(text: &str, precision: &Precision) -> InfluxResult<Self>
+    {
+        line::parse_line(text, 1, precision)?
+            .ok_or_else(|| "Line contains no measurement".into())
+    }
+
+    /// Name of this Measurement
+    pub fn name(&self) -> &str
+    {
+        &self.name
+    }
+
+    /// Tags of this Measurement, excluding default tags of the record or client
+    pub fn tags(&self) -> &BTreeMap<String, String>
     {
-        self.timestamp = timestamp; self
+        &self.tags
+    }
+
+    /// Fields of this Measurement, to be read back through `TryFrom` conversions of [Value](enum.Value.html)
+    pub fn fields(&self) -> &BTreeMap<String, Value>
+    {
+        &self.fields
+    }
+
+    /// Set datetime of this Measurement, from any of the types listed at [IntoTimestamp](trait.IntoTimestamp.html)
+    pub fn timestamp<T: IntoTimestamp>(&mut self, timestamp: T) -> &mut Self
+    {
+        self.timestamp = Some(timestamp.into_timestamp()); self
+    }
+
+    /// Stamp this Measurement with the current client time
+    pub fn client_timestamp(&mut self) -> &mut Self
+    {
+        self.timestamp = Some(Utc::now()); self
+    }
+
+    /// Send this Measurement without timestamp, so InfluxDB assigns its own time at the moment of writing. Note that
+    /// for backlogged records this will be the time of the replay, not the time of the original write attempt.
+    pub fn server_timestamp(&mut self) -> &mut Self
+    {
+        self.timestamp = None; self
     }
 
     /// Add a tag to this Measurement
@@ -52,49 +123,565 @@ impl Measurement
         self
     }
 
-    /// Add a value field to this Measurement
-    pub fn field<V: Into<Value>>(&mut self, key: &str, value: V) -> &mut Self
+    /// Add a value field to this Measurement. Optional values that are `None` leave the field out.
+    pub fn field<V: IntoFieldValue>(&mut self, key: &str, value: V) -> &mut Self
+    {
+        if let Some(value) = value.into_field_value() {
+            self.fields.insert(key.to_owned(), value);
+        }
+
+        self
+    }
+
+    /// Encode this Measurement as a single line of InfluxDB line protocol, escaping the measurement name, tag keys,
+    /// tag values and field keys, quoting string fields and suffixing integer fields. The timestamp gets truncated to
+    /// the given precision and fails to encode if it lies outside of the range supported by InfluxDB.
+    ///
+    /// ## Examples
+    ///
+    /// Taken from the special characters section of the official line protocol reference:
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    /// use influxc::Precision;
+    ///
+    /// use chrono::TimeZone;
+    ///
+    /// let ts  = chrono::Utc.timestamp_opt(1556813561, 98000000).unwrap();
+    /// let mut rec = Record::new("org", "bucket");
+    ///
+    /// let golden = [
+    ///     (rec.measurement("my Measurement").field("fieldKey", "string value".to_owned()).timestamp(ts).to_line(&Precision::Nanoseconds).unwrap(),
+    ///         r#"my\ Measurement fieldKey="string value" 1556813561098000000"#),
+    ///
+    ///     (rec.measurement("myMeasurement").field("fieldKey", r#""string" within a string"#.to_owned()).timestamp(ts).to_line(&Precision::Nanoseconds).unwrap(),
+    ///         r#"myMeasurement fieldKey="\"string\" within a string" 1556813561098000000"#),
+    ///
+    ///     (rec.measurement("myMeasurement").tag("tag Key1", "tag Value1").tag("tag Key2", "tag Value2").field("fieldKey", 100).timestamp(ts).to_line(&Precision::Nanoseconds).unwrap(),
+    ///         r#"myMeasurement,tag\ Key1=tag\ Value1,tag\ Key2=tag\ Value2 fieldKey=100i 1556813561098000000"#),
+    ///
+    ///     (rec.measurement("myMeasurement").tag("tagKey", "🍭").field("fieldKey", "Launch 🚀".to_owned()).timestamp(ts).to_line(&Precision::Nanoseconds).unwrap(),
+    ///         r#"myMeasurement,tagKey=🍭 fieldKey="Launch 🚀" 1556813561098000000"#),
+    ///
+    ///     (rec.measurement("my,Measurement").tag("tag=Key", "a,b=c").field("field Key", 1.5).timestamp(ts).to_line(&Precision::Seconds).unwrap(),
+    ///         r#"my\,Measurement,tag\=Key=a\,b\=c field\ Key=1.5 1556813561"#),
+    ///
+    ///     (rec.measurement(r"my\Measurement").field("fieldKey", r"\".to_owned()).timestamp(ts).to_line(&Precision::Milliseconds).unwrap(),
+    ///         r#"my\\Measurement fieldKey="\\" 1556813561098"#),
+    ///
+    ///     (rec.measurement("myMeasurement").field("fieldKey", true).timestamp(ts).to_line(&Precision::Microseconds).unwrap(),
+    ///         r#"myMeasurement fieldKey=true 1556813561098000"#),
+    ///
+    ///     (rec.measurement("myMeasurement").field("fieldKey", 12485903u64).timestamp(ts).to_line(&Precision::Seconds).unwrap(),
+    ///         r#"myMeasurement fieldKey=12485903u 1556813561"#),
+    ///
+    ///     (rec.measurement("myMeasurement").field("fieldKey", 1.0).server_timestamp().to_line(&Precision::Seconds).unwrap(),
+    ///         r#"myMeasurement fieldKey=1"#),
+    ///
+    ///     (rec.measurement("home").tag("room", "Living Room").field("temp", 21.1).field("hum", 35.9).field("co", 0).field("on", true).timestamp(ts).to_line(&Precision::Seconds).unwrap(),
+    ///         r#"home,room=Living\ Room co=0i,hum=35.9,on=true,temp=21.1 1556813561"#),
+    /// ];
+    ///
+    /// for (line, expected) in golden.iter() {
+    ///     assert_eq!(line, expected);
+    /// }
+    /// ```
+    pub fn to_line(&self, precision: &Precision) -> InfluxResult<String>
+    {
+        self.to_line_rounded(precision, Rounding::default())
+    }
+
+    /// Same as [to_line](#method.to_line), but with explicit rounding of the timestamp to the given precision.
+    pub fn to_line_rounded(&self, precision: &Precision, rounding: Rounding) -> InfluxResult<String>
+    {
+        let mut buffer = Vec::new();
+
+        self.write_line(&mut buffer, precision, rounding)?;
+
+        Ok(String::from_utf8(buffer).expect("line protocol encoding produced invalid UTF-8"))
+    }
+
+    /// Stream this Measurement as line protocol into the writer, without any intermediate allocation and without
+    /// terminating newline. Nothing gets written if the timestamp is out of range.
+    pub fn write_line<W: Write>(&self, writer: &mut W, precision: &Precision, rounding: Rounding) -> InfluxResult<()>
+    {
+        self.write_line_tagged(writer, precision, rounding, &BTreeMap::new())
+    }
+
+    /// Same as [write_line](#method.write_line), with default tags merged into the own ones, which take precedence.
+    pub(crate) fn write_line_tagged<W>(&self, writer: &mut W, precision: &Precision, rounding: Rounding, defaults: &Tags) -> InfluxResult<()>
+        where W: Write
+    {
+        let timestamp = match &self.timestamp
+        {
+            Some(ts) => { Some(precision.to_timestamp(ts, rounding)?) }
+            None     => { None }
+        };
+
+        line::write_measurement(writer, &self.name)?;
+
+        for (key, value) in self.tags_with(defaults)
+        {
+            writer.write_all(b",")?;
+            line::write_key(writer, key)?;
+            writer.write_all(b"=")?;
+            line::write_key(writer, value)?;
+        }
+
+        for (idx, (key, value)) in self.fields.iter().enumerate()
+        {
+            writer.write_all(if idx == 0 { b" " } else { b"," })?;
+            line::write_key(writer, key)?;
+            writer.write_all(b"=")?;
+            line::write_value(writer, value)?;
+        }
+
+        if let Some(ts) = timestamp {
+            write!(writer, " {}", ts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Tags of this Measurement merged with the defaults in key order, the own ones taking precedence
+    pub(crate) fn tags_with<'m>(&'m self, defaults: &'m Tags) -> MergedTags<'m>
+    {
+        MergedTags {
+            own:      self.tags.iter().peekable(),
+            defaults: defaults.iter().peekable(),
+        }
+    }
+
+    /// Copy of this Measurement with the default tags merged into its own ones
+    pub(crate) fn with_tags(&self, defaults: &Tags) -> Self
+    {
+        let mut this = self.clone();
+
+        for (key, value) in defaults.iter() {
+            this.tags.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+
+        this
+    }
+
+    /// Series key as InfluxDB builds it: the escaped measurement name followed by the sorted, escaped tag set
+    pub(crate) fn series_key(&self, defaults: &Tags) -> InfluxResult<Vec<u8>>
+    {
+        let mut key = Vec::new();
+
+        line::write_measurement(&mut key, &self.name)?;
+
+        for (tag, value) in self.tags_with(defaults)
+        {
+            key.push(b',');
+            line::write_key(&mut key, tag)?;
+            key.push(b'=');
+            line::write_key(&mut key, value)?;
+        }
+
+        Ok(key)
+    }
+
+    /// Append every offense against what InfluxDB accepts to the list of errors, referencing the measurement by the
+    /// given index.
+    pub(crate) fn validate(&self, index: usize, defaults: &Tags, errors: &mut Vec<ValidationError>)
+    {
+        if self.name.is_empty() {
+            errors.push(ValidationError::new(index, None, ValidationKind::EmptyName));
+        }
+        else if self.name.starts_with('_') {
+            errors.push(ValidationError::new(index, None, ValidationKind::ReservedKey));
+        }
+
+        if self.name.contains('\n') {
+            errors.push(ValidationError::new(index, None, ValidationKind::Newline));
+        }
+
+        if self.fields.is_empty() {
+            errors.push(ValidationError::new(index, None, ValidationKind::NoFields));
+        }
+
+        for (key, value) in self.tags_with(defaults)
+        {
+            validate_key(index, key, errors);
+
+            if value.is_empty() {
+                errors.push(ValidationError::new(index, Some(key), ValidationKind::EmptyTagValue));
+            }
+            else if value.contains('\n') {
+                errors.push(ValidationError::new(index, Some(key), ValidationKind::Newline));
+            }
+        }
+
+        for (key, value) in self.fields.iter()
+        {
+            validate_key(index, key, errors);
+
+            if let Value::Float(v) = value
+            {
+                if ! v.is_finite() {
+                    errors.push(ValidationError::new(index, Some(key), ValidationKind::NonFiniteFloat));
+                }
+            }
+        }
+    }
+}
+
+
+/// Tag set as sorted by key
+pub(crate) type Tags = BTreeMap<String, String>;
+
+
+/// Iterator merging two sorted tag sets, the first overriding the second on equal keys
+pub(crate) struct MergedTags<'m>
+{
+    own:      Peekable<btree_map::Iter<'m, String, String>>,
+    defaults: Peekable<btree_map::Iter<'m, String, String>>,
+}
+
+
+impl<'m> Iterator for MergedTags<'m>
+{
+    type Item = (&'m String, &'m String);
+
+    fn next(&mut self) -> Option<Self::Item>
+    {
+        let order = match (self.own.peek(), self.defaults.peek())
+        {
+            (Some(own), Some(default)) => { own.0.cmp(default.0) }
+            (Some(_),   None)          => { Ordering::Less }
+            (None,      Some(_))       => { Ordering::Greater }
+            (None,      None)          => { return None; }
+        };
+
+        match order
+        {
+            Ordering::Less    => { self.own.next() }
+            Ordering::Greater => { self.defaults.next() }
+            Ordering::Equal   => { self.defaults.next(); self.own.next() }
+        }
+    }
+}
+
+
+/// Builder for a [Measurement](struct.Measurement.html) borrowing the [Record](struct.Record.html) it goes into. The
+/// measurement only gets added once completed, either by [commit](#method.commit) or by dropping the builder, and is
+/// stamped with the client time at that moment unless a timestamp was set explicitly or the record leaves stamping to
+/// the server. Measurements missing a field are not added; on drop they are discarded with an error logged.
+///
+/// ## Examples
+///
+/// ```rust
+/// use influxc::Record;
+///
+/// let mut rec = Record::new("org", "bucket");
+///
+/// // added when the builder is dropped at the end of the statement
+/// rec.build("sensor1").tag("floor", "second").field("temp", 21.5);
+///
+/// // never added
+/// let builder = rec.build("sensor2").field("temp", 20.5);
+/// builder.cancel();
+///
+/// // rejected for lack of a required field
+/// let result = rec.build("sensor3")
+///     .require(&["temp", "brightness"])
+///     .field("temp", 19.5)
+///     .commit();
+///
+/// assert!(result.is_err());
+/// assert_eq!(rec.measurements().len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct MeasurementBuilder<'r>
+{
+    record:      &'r mut Record,
+    measurement: Option<Measurement>,
+    stamp:       bool,
+    required:    Vec<String>,
+}
+
+
+impl<'r> MeasurementBuilder<'r>
+{
+    pub(crate) fn new(record: &'r mut Record, name: &str, stamp: bool) -> Self
+    {
+        Self {
+            record,
+            measurement: Some(Measurement::new(name, None)),
+            stamp,
+            required: Vec::new(),
+        }
+    }
+
+    /// Set datetime of the Measurement, instead of stamping it on completion
+    pub fn timestamp<T: IntoTimestamp>(mut self, timestamp: T) -> Self
+    {
+        self.stamp = false;
+        self.inner().timestamp(timestamp);
+        self
+    }
+
+    /// Send the Measurement without timestamp, so InfluxDB assigns its own time at the moment of writing
+    pub fn server_timestamp(mut self) -> Self
     {
-        self.fields.insert(key.to_owned(), value.into());
+        self.stamp = false;
+        self.inner().server_timestamp();
         self
     }
 
-    pub(crate) fn to_line(&self, precision: &Precision) -> String
+    /// Add a tag to the Measurement
+    pub fn tag(mut self, key: &str, value: &str) -> Self
     {
-        let mut line = self.name.to_owned();
+        self.inner().tag(key, value); self
+    }
+
+    /// Add a value field to the Measurement. Optional values that are `None` leave the field out.
+    pub fn field<V: IntoFieldValue>(mut self, key: &str, value: V) -> Self
+    {
+        self.inner().field(key, value); self
+    }
+
+    /// Require the given fields to be present for the Measurement to be added
+    pub fn require(mut self, keys: &[&str]) -> Self
+    {
+        self.required.extend(keys.iter().map(|k| (*k).to_owned())); self
+    }
 
-        if ! self.tags.is_empty()
+    /// Complete the Measurement and add it to the record. Fails if it has no fields or misses a required one, in
+    /// which case it is discarded.
+    pub fn commit(mut self) -> InfluxResult<()>
+    {
+        match self.measurement.take()
         {
-            let tagline = self.tags.iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<String>>()
-                .join(",");
+            Some(msrmt) => { self.finish(msrmt) }
+            None        => { Ok(()) }
+        }
+    }
+
+    /// Discard the Measurement without adding it to the record
+    pub fn cancel(mut self)
+    {
+        self.measurement = None;
+    }
 
-            line += ",";
-            line += &tagline;
+    fn inner(&mut self) -> &mut Measurement
+    {
+        self.measurement.as_mut()
+            .expect("measurement is only taken on completion")
+    }
+
+    fn finish(&mut self, mut msrmt: Measurement) -> InfluxResult<()>
+    {
+        let index      = self.record.measurements.len();
+        let mut errors = Vec::new();
+
+        if msrmt.fields.is_empty() {
+            errors.push(ValidationError::new(index, None, ValidationKind::NoFields));
         }
 
-        if ! self.fields.is_empty()
+        for key in self.required.iter()
         {
-            let fieldline = self.fields.iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<String>>()
-                .join(",");
+            if ! msrmt.fields.contains_key(key) {
+                errors.push(ValidationError::new(index, Some(key), ValidationKind::MissingField));
+            }
+        }
 
-            line += " ";
-            line += &fieldline;
+        if ! errors.is_empty() {
+            return Err(InfluxError::Invalid(errors));
         }
 
-        line += " ";
+        if self.stamp {
+            msrmt.timestamp = Some(Utc::now());
+        }
 
-        match precision
+        self.record.measurements.push(msrmt);
+
+        Ok(())
+    }
+}
+
+
+impl<'r> Drop for MeasurementBuilder<'r>
+{
+    fn drop(&mut self)
+    {
+        if let Some(msrmt) = self.measurement.take()
         {
-            Precision::Nanoseconds  => { line += &self.timestamp.timestamp_nanos().to_string();  }
-            Precision::Microseconds => { line += &(self.timestamp.timestamp_nanos() * 1000).to_string(); }
-            Precision::Milliseconds => { line += &self.timestamp.timestamp_millis().to_string(); }
-            Precision::Seconds      => { line += &(self.timestamp.timestamp() ).to_string(); }
+            if let Err(e) = self.finish(msrmt) {
+                error!("Discarding incomplete measurement: {}", e);
+            }
         }
+    }
+}
+
+
+/// Conversion of a value into the tags, fields and timestamp of a [Measurement](struct.Measurement.html), as used by
+/// [Record::push](struct.Record.html#method.push). Usually implemented through `#[derive(InfluxMeasurement)]`, which
+/// requires the `derive` feature.
+///
+/// ## Examples
+///
+/// ```rust
+/// use influxc::Record;
+/// use influxc::Measurement;
+/// use influxc::IntoMeasurement;
+///
+/// struct Sensor
+/// {
+///     floor: String,
+///     temp:  f64,
+/// }
+///
+/// impl IntoMeasurement for Sensor
+/// {
+///     fn measurement_name(&self) -> &str
+///     {
+///         "sensor"
+///     }
+///
+///     fn fill_measurement(self, measurement: &mut Measurement)
+///     {
+///         measurement.tag("floor", &self.floor).field("temp", self.temp);
+///     }
+/// }
+///
+/// let mut rec = Record::new("org", "bucket")
+///     .server_timestamps();
+///
+/// rec.push(Sensor {floor: "second".to_owned(), temp: 21.5});
+///
+/// let mut buffer = Vec::new();
+/// rec.write_lines(&mut buffer).unwrap();
+///
+/// assert_eq!(buffer, b"sensor,floor=second temp=21.5\n");
+/// ```
+pub trait IntoMeasurement
+{
+    /// Name of the measurement to record this value under
+    fn measurement_name(&self) -> &str;
+
+    /// Add tags, fields and, if the value carries one, the timestamp to the measurement
+    fn fill_measurement(self, measurement: &mut Measurement);
+}
+
+fn validate_key(index: usize, key: &str, errors: &mut Vec<ValidationError>)
+{
+    if key.is_empty() {
+        errors.push(ValidationError::new(index, Some(key), ValidationKind::EmptyKey));
+    }
+    else if key == "time" || key.starts_with('_') {
+        errors.push(ValidationError::new(index, Some(key), ValidationKind::ReservedKey));
+    }
+
+    if key.contains('\n') {
+        errors.push(ValidationError::new(index, Some(key), ValidationKind::Newline));
+    }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use std::thread;
+    use std::time::Duration;
+
+
+    /// Time that certainly passes between building a measurement and completing it
+    const PAUSE: Duration = Duration::from_millis(20);
+
+
+    fn stamp(rec: &Record) -> Option<DateTime>
+    {
+        rec.measurements().last().unwrap().timestamp
+    }
+
+
+    #[test]
+    fn stamps_on_commit()
+    {
+        let mut rec = Record::new("org", "bucket");
+
+        let builder = rec.build("sensor").field("temp", 21.5);
+
+        thread::sleep(PAUSE);
+
+        let completed = Utc::now();
+
+        builder.commit().unwrap();
+
+        assert!(stamp(&rec).unwrap() >= completed);
+    }
+
+
+    #[test]
+    fn stamps_on_drop()
+    {
+        let mut rec = Record::new("org", "bucket");
+
+        let builder = rec.build("sensor").field("temp", 21.5);
+
+        thread::sleep(PAUSE);
+
+        let completed = Utc::now();
+
+        drop(builder);
+
+        assert_eq!(rec.measurements().len(), 1);
+        assert!(stamp(&rec).unwrap() >= completed);
+    }
+
+
+    #[test]
+    fn adds_on_drop_but_not_on_cancel()
+    {
+        let mut rec = Record::new("org", "bucket");
+
+        rec.build("sensor").field("temp", 21.5);
+        rec.build("sensor").field("temp", 20.5).cancel();
+        rec.build("sensor");
+
+        assert_eq!(rec.measurements().len(), 1);
+        assert_eq!(rec.measurements()[0].fields()["temp"].to_string(), "21.5");
+    }
+
+
+    #[test]
+    fn leaves_server_timestamps_unstamped()
+    {
+        let mut rec = Record::new("org", "bucket").server_timestamps();
+
+        rec.build("sensor").field("temp", 21.5);
+
+        assert_eq!(stamp(&rec), None);
+
+        rec.build("sensor").field("temp", 21.5).commit().unwrap();
+
+        assert_eq!(stamp(&rec), None);
+
+        let mut rec = Record::new("org", "bucket");
+
+        rec.build("sensor").field("temp", 21.5).server_timestamp();
+
+        assert_eq!(stamp(&rec), None);
+    }
+
+
+    #[test]
+    fn keeps_explicit_timestamp()
+    {
+        let mut rec = Record::new("org", "bucket");
+
+        let builder = rec.build("sensor")
+            .field("temp", 21.5)
+            .timestamp((1556813561, Precision::Seconds));
+
+        thread::sleep(PAUSE);
+
+        builder.commit().unwrap();
 
-        line
+        assert_eq!(stamp(&rec).unwrap().timestamp(), 1556813561);
     }
 }
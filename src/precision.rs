@@ -1,16 +1,27 @@
 //!
 //! Precision of the Measurement being Stored/Loaded
 //!
+use crate::DateTime;
+
 use crate::InfluxError;
+use crate::InfluxResult;
 
 use std::fmt;
 
 
+/// Earliest instant InfluxDB is able to store, in nanoseconds since epoch
+const MIN_NANOS: i128 = -9_223_372_036_854_775_806;
+
+/// Latest instant InfluxDB is able to store, in nanoseconds since epoch
+const MAX_NANOS: i128 = 9_223_372_036_854_775_806;
+
+
 /// The time resolution the bucket is to keep its measurements
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub enum Precision
 {
     /// Self explanatory nanoseconds
+    #[default]
     Nanoseconds,
 
     /// Self explanatory microseconds
@@ -24,6 +35,98 @@ pub enum Precision
 }
 
 
+/// How to treat the remainder when a timestamp gets coarsened to a lower precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Rounding
+{
+    /// Drop the remainder, rounding towards the epoch
+    #[default]
+    Truncate,
+
+    /// Round to the nearest unit, halves away from the epoch
+    Round,
+
+    /// Round towards the past, also for instants before the epoch
+    Floor,
+}
+
+
+impl Precision
+{
+    /// Convert a datetime into an integer timestamp in units of this precision. Fails for instants outside of the range
+    /// InfluxDB is able to store, which is roughly the years 1677 to 2262 regardless of precision.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Precision;
+    /// use influxc::Rounding;
+    ///
+    /// use chrono::TimeZone;
+    ///
+    /// let dt = chrono::Utc.timestamp_opt(-1, 999_600_000).unwrap();
+    ///
+    /// assert_eq!(Precision::Microseconds.to_timestamp(&dt, Rounding::Truncate).unwrap(), -400);
+    /// assert_eq!(Precision::Seconds.to_timestamp(&dt, Rounding::Truncate).unwrap(), 0);
+    /// assert_eq!(Precision::Seconds.to_timestamp(&dt, Rounding::Floor).unwrap(), -1);
+    /// assert_eq!(Precision::Milliseconds.to_timestamp(&dt, Rounding::Round).unwrap(), 0);
+    ///
+    /// let early = chrono::Utc.with_ymd_and_hms(1650, 1, 1, 0, 0, 0).unwrap();
+    ///
+    /// assert!(Precision::Seconds.to_timestamp(&early, Rounding::Truncate).is_err());
+    /// ```
+    pub fn to_timestamp(&self, datetime: &DateTime, rounding: Rounding) -> InfluxResult<i64>
+    {
+        let nanos = i128::from(datetime.timestamp()) * 1_000_000_000 + i128::from(datetime.timestamp_subsec_nanos());
+        let unit  = self.nanos_per_unit();
+
+        let timestamp = match rounding
+        {
+            Rounding::Truncate => { nanos / unit }
+            Rounding::Floor    => { nanos.div_euclid(unit) }
+
+            Rounding::Round => {
+                if (nanos % unit).abs() * 2 >= unit {
+                    nanos / unit + nanos.signum()
+                } else {
+                    nanos / unit
+                }
+            }
+        };
+
+        if ! (MIN_NANOS..=MAX_NANOS).contains(&nanos) || ! (MIN_NANOS..=MAX_NANOS).contains(&(timestamp * unit)) {
+            return Err(format!("Timestamp {} is outside of the range supported by InfluxDB", datetime).into());
+        }
+
+        Ok(timestamp as i64)
+    }
+
+    /// Convert an integer timestamp in units of this precision back into a datetime. Fails for instants outside of the
+    /// range InfluxDB is able to store.
+    pub fn to_datetime(&self, timestamp: i64) -> InfluxResult<DateTime>
+    {
+        let nanos = i128::from(timestamp) * self.nanos_per_unit();
+
+        if ! (MIN_NANOS..=MAX_NANOS).contains(&nanos) {
+            return Err(format!("Timestamp {}{} is outside of the range supported by InfluxDB", timestamp, self).into());
+        }
+
+        Ok(DateTime::from_timestamp_nanos(nanos as i64))
+    }
+
+    pub(crate) fn nanos_per_unit(&self) -> i128
+    {
+        match self
+        {
+            Precision::Nanoseconds  => 1,
+            Precision::Microseconds => 1_000,
+            Precision::Milliseconds => 1_000_000,
+            Precision::Seconds      => 1_000_000_000,
+        }
+    }
+}
+
+
 impl std::str::FromStr for Precision
 {
     type Err = InfluxError;
@@ -60,10 +163,35 @@ impl fmt::Display for Precision
 }
 
 
-impl Default for Precision
+impl std::str::FromStr for Rounding
+{
+    type Err = InfluxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let r = match s
+        {
+            "trunc" => Rounding::Truncate,
+            "round" => Rounding::Round,
+            "floor" => Rounding::Floor,
+
+            _ => { return Err(format!("Invalid rounding: {}", s).into()) }
+        };
+
+        Ok(r)
+    }
+}
+
+
+impl fmt::Display for Rounding
 {
-    fn default() -> Self
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
     {
-        Self::Nanoseconds
+        match self
+        {
+            Rounding::Truncate => "trunc".fmt(f),
+            Rounding::Round    => "round".fmt(f),
+            Rounding::Floor    => "floor".fmt(f),
+        }
     }
 }
@@ -1,44 +1,126 @@
 //!
 //! Unit of recording that can contain multiple Measurements
 //!
+use crate::line;
+use crate::Compression;
+use crate::Target;
+use crate::Rounding;
 use crate::Precision;
 use crate::Measurement;
+use crate::MeasurementBuilder;
+use crate::SerdeMapping;
+use crate::IntoMeasurement;
 
+use crate::measurement::Tags;
+
+use crate::Utc;
+use crate::DateTime;
+
+use crate::InfluxError;
 use crate::InfluxResult;
+use crate::ValidationKind;
+use crate::ValidationError;
 
 use crate::FlateLevel;
 use crate::FlateGzipBuilder;
 
-use crate::ReqwRequestBuilder;
-
 use std::io::Write;
+use std::io::BufWriter;
+
+use std::ops::Range;
+use std::collections::HashMap;
 
 
 /// Coarse unit of recording. It keeps track of the organization, bucket and precision which are inherent to the concept
 /// of "schema" in SQL lingo.
 /// It gets filled with measurements that provide the "table name" (measurement) as well as "indexed columns" (tags) and
 /// values.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Record
 {
-    pub(crate) org:          String,
-    pub(crate) bucket:       String,
+    pub(crate) org:          Target,
+    pub(crate) bucket:       Target,
     pub(crate) precision:    Precision,
+    pub(crate) rounding:     Rounding,
     pub(crate) measurements: Vec<Measurement>,
+    pub(crate) default_tags: Tags,
+
+    server_timestamps: bool,
 }
 
 
 impl Record
 {
-    /// Create a new measurement by specifying the owning organization and the bucket
+    /// Create a new measurement by specifying the owning organization and the bucket by name
     pub fn new(org: &str, bucket: &str) -> Self
+    {
+        Self::targeting(Target::name(org), Target::name(bucket))
+    }
+
+    /// Create a new measurement by specifying the owning organization and the bucket, each either by name or by ID.
+    /// The organization ID gets sent as `orgID`, the bucket ID in place of its name.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    /// use influxc::Target;
+    ///
+    /// let rec = Record::targeting(Target::id("0261d8287f4d6000"), Target::name("telemetry"));
+    ///
+    /// assert!(rec.to_string().starts_with("Record(org=id:0261d8287f4d6000, bucket=telemetry,"));
+    /// ```
+    pub fn targeting(org: Target, bucket: Target) -> Self
     {
         Self {
-            org:          org.to_owned(),
-            bucket:       bucket.to_owned(),
+            org,
+            bucket,
             precision:    Precision::default(),
-            measurements: Vec::new()
+            rounding:     Rounding::default(),
+            measurements: Vec::new(),
+            default_tags: Tags::new(),
+
+            server_timestamps: false,
+        }
+    }
+
+    /// Parse line protocol text, one measurement per line, into a record. Empty lines and `#` comments are skipped,
+    /// timestamps are interpreted in units of the given precision.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    /// use influxc::Precision;
+    /// use influxc::InfluxError;
+    ///
+    /// let text = "# sensor readings\n\
+    ///             sensor1,floor=second temp=123i,ok=TRUE 1556813561098\n\
+    ///             \n\
+    ///             sensor2,floor=second temp=321u 1556813561099\n";
+    ///
+    /// let record = Record::from_lines("org", "bucket", Precision::Milliseconds, text).unwrap();
+    ///
+    /// assert_eq!(record.to_string().lines().count(), 3);
+    ///
+    /// match Record::from_lines("org", "bucket", Precision::Seconds, "a b=1\na b=\"open") {
+    ///     Err(InfluxError::LineProtocol(e)) => { assert_eq!((e.line(), e.column()), (2, 5)); }
+    ///     _                                 => { panic!("expected syntax error"); }
+    /// }
+    /// ```
+    pub fn from_lines(org: &str, bucket: &str, precision: Precision, text: &str) -> InfluxResult<Self>
+    {
+        let mut this = Self::new(org, bucket)
+            .precision(precision);
+
+        for (idx, text) in text.lines().enumerate()
+        {
+            if let Some(msrmt) = line::parse_line(text, idx + 1, &this.precision)? {
+                this.measurements.push(msrmt);
+            }
         }
+
+        Ok(this)
     }
 
     /// Set precision. It otherwise defaults to nanoseconds.
@@ -47,55 +129,503 @@ impl Record
         self.precision = precision; self
     }
 
+    /// Set how timestamps get rounded when coarsened to the precision. It otherwise defaults to truncation.
+    pub fn rounding(mut self, rounding: Rounding) -> Self
+    {
+        self.rounding = rounding; self
+    }
+
+    /// Add a tag that gets merged into every measurement of this record when encoded. Tags set on the measurement
+    /// itself take precedence.
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    /// use influxc::Precision;
+    ///
+    /// let mut record = Record::new("org", "bucket")
+    ///     .precision(Precision::Seconds)
+    ///     .default_tag("host", "alpha")
+    ///     .default_tag("region", "eu");
+    ///
+    /// record.measurement("cpu")
+    ///     .tag("host", "beta")
+    ///     .field("load", 0.5)
+    ///     .timestamp(chrono::DateTime::from_timestamp(1, 0).unwrap());
+    ///
+    /// let mut buf = Vec::new();
+    /// record.write_lines(&mut buf).unwrap();
+    ///
+    /// assert_eq!(String::from_utf8(buf).unwrap(), "cpu,host=beta,region=eu load=0.5 1\n");
+    /// ```
+    pub fn default_tag(mut self, key: &str, value: &str) -> Self
+    {
+        self.default_tags.insert(key.to_owned(), value.to_owned()); self
+    }
+
+    /// Leave timestamping of new measurements to the InfluxDB server instead of stamping them with the client time at
+    /// creation. Useful on devices with unreliable clocks. Individual measurements can still set their own timestamp.
+    pub fn server_timestamps(mut self) -> Self
+    {
+        self.server_timestamps = true; self
+    }
+
     /// Add and return a measurement for further parametrization.
     pub fn measurement<'r>(&'r mut self, name: &str) -> &'r mut Measurement
     {
-        self.measurements.push(Measurement::new(name));
+        self.measurements.push(Measurement::new(name, self.stamp()));
         self.measurements.last_mut().unwrap()
     }
+
+    /// Add a measurement from any serializable struct or map, distributing its keys onto tags, fields and timestamp
+    /// as the mapping dictates. Nothing is added if serialization fails or leaves the measurement without fields, as
+    /// for unit values or ones whose fields are all `None`.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    /// use influxc::Precision;
+    /// use influxc::SerdeMapping;
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Position { lat: f64, lon: f64 }
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Reading
+    /// {
+    ///     device:   String,
+    ///     floor:    u8,
+    ///     temp:     Option<f32>,
+    ///     humidity: Option<f32>,
+    ///     position: Position,
+    ///     taken:    String,
+    /// }
+    ///
+    /// let reading = Reading {
+    ///     device:   "dev-7".to_owned(),
+    ///     floor:    2,
+    ///     temp:     Some(21.7),
+    ///     humidity: None,
+    ///     position: Position {lat: -33.45, lon: -70.66},
+    ///     taken:    "2019-05-02T16:12:41Z".to_owned(),
+    /// };
+    ///
+    /// let mapping = SerdeMapping::new()
+    ///     .tags(&["device", "floor"])
+    ///     .timestamp("taken")
+    ///     .separator(".");
+    ///
+    /// let mut rec = Record::new("org", "bucket")
+    ///     .precision(Precision::Seconds);
+    ///
+    /// rec.push_serialized("reading", &reading, &mapping).unwrap();
+    ///
+    /// let mut buffer = Vec::new();
+    /// rec.write_lines(&mut buffer).unwrap();
+    ///
+    /// assert_eq!(String::from_utf8(buffer).unwrap(), "reading,device=dev-7,floor=2 position.lat=-33.45,position.lon=-70.66,temp=21.7 1556813561\n");
+    /// ```
+    pub fn push_serialized<T>(&mut self, name: &str, value: &T, mapping: &SerdeMapping)
+        -> InfluxResult<&mut Measurement>
+        where T: serde::Serialize + ?Sized
+    {
+        let mut msrmt = Measurement::new(name, self.stamp());
+
+        mapping.fill(&mut msrmt, value, &self.precision)?;
+
+        if msrmt.fields.is_empty() {
+            return Err(InfluxError::Invalid(vec![
+                ValidationError::new(self.measurements.len(), None, ValidationKind::NoFields)
+            ]));
+        }
+
+        self.measurements.push(msrmt);
+        Ok(self.measurements.last_mut().unwrap())
+    }
+
+    /// Start building a measurement that only gets added to this record once completed, and gets stamped at that
+    /// moment. See [MeasurementBuilder](struct.MeasurementBuilder.html).
+    pub fn build(&mut self, name: &str) -> MeasurementBuilder<'_>
+    {
+        let stamp = ! self.server_timestamps;
+
+        MeasurementBuilder::new(self, name, stamp)
+    }
+
+    /// Add a measurement from any value that knows how to map itself onto one, like structs deriving
+    /// `InfluxMeasurement`. Returns the measurement for further parametrization.
+    pub fn push<T: IntoMeasurement>(&mut self, item: T) -> &mut Measurement
+    {
+        let name  = item.measurement_name().to_owned();
+        let msrmt = self.measurement(&name);
+
+        item.fill_measurement(msrmt);
+
+        msrmt
+    }
+
+    /// Measurements collected so far
+    pub fn measurements(&self) -> &[Measurement]
+    {
+        &self.measurements
+    }
+
+    /// Check all measurements for content InfluxDB would reject, like measurements without fields, empty tag values,
+    /// reserved keys or NaN floats. Every offense found is reported, pointing at the measurement index and key.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    /// use influxc::InfluxError;
+    /// use influxc::ValidationKind;
+    ///
+    /// let mut rec = Record::new("org", "bucket");
+    ///
+    /// rec.measurement("sensor1").tag("floor", "second").field("temp", 21.5);
+    /// rec.measurement("sensor2").tag("floor", "").field("_field", f64::NAN);
+    /// rec.measurement("sensor3").tag("floor", "first");
+    ///
+    /// match rec.validate() {
+    ///     Err(InfluxError::Invalid(errors)) => {
+    ///         let found = errors.iter()
+    ///             .map(|e| (e.index(), e.key(), e.kind()))
+    ///             .collect::<Vec<_>>();
+    ///
+    ///         assert_eq!(found, vec![
+    ///             (1, Some("floor"),  ValidationKind::EmptyTagValue),
+    ///             (1, Some("_field"), ValidationKind::ReservedKey),
+    ///             (1, Some("_field"), ValidationKind::NonFiniteFloat),
+    ///             (2, None,           ValidationKind::NoFields),
+    ///         ]);
+    ///     }
+    ///     _ => { panic!("expected validation errors"); }
+    /// }
+    /// ```
+    pub fn validate(&self) -> InfluxResult<()>
+    {
+        let mut errors = Vec::new();
+
+        for (index, measurement) in self.measurements.iter().enumerate() {
+            measurement.validate(index, &self.default_tags, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(InfluxError::Invalid(errors))
+        }
+    }
+
+    /// Merge measurements sharing name, tag set and timestamp into a single one, in the position of the first. Saves
+    /// payload and leaves nothing for InfluxDB to merge last-write-wins. Timestamps count as they get written, coarsened
+    /// to the precision of the record, and the merged measurement keeps the one of the first. Default tags count towards
+    /// the tag set, and measurements left to be stamped by the server are merged as well, as they get the same server
+    /// time.
+    ///
+    /// Fields set to different values by different measurements are reported as
+    /// [ValidationKind::ConflictingField](enum.ValidationKind.html#variant.ConflictingField), pointing at the later
+    /// measurement, in which case the record is left untouched.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    /// use influxc::Precision;
+    /// use influxc::InfluxError;
+    /// use influxc::ValidationKind;
+    ///
+    /// let mut rec = Record::new("org", "bucket")
+    ///     .server_timestamps()
+    ///     .default_tag("host", "alpha");
+    ///
+    /// rec.measurement("cpu").tag("core", "0").field("user", 0.5);
+    /// rec.measurement("mem").field("free", 1024);
+    /// rec.measurement("cpu").tag("core", "0").tag("host", "alpha").field("system", 0.25).field("user", 0.5);
+    ///
+    /// rec.compact().unwrap();
+    ///
+    /// let mut buffer = Vec::new();
+    /// rec.write_lines(&mut buffer).unwrap();
+    ///
+    /// assert_eq!(buffer, b"cpu,core=0,host=alpha system=0.25,user=0.5\nmem,host=alpha free=1024i\n");
+    ///
+    /// rec.measurement("mem").field("free", 512);
+    ///
+    /// match rec.compact() {
+    ///     Err(InfluxError::Invalid(errors)) => {
+    ///         assert_eq!((errors[0].index(), errors[0].key()), (2, Some("free")));
+    ///         assert_eq!(errors[0].kind(), ValidationKind::ConflictingField);
+    ///     }
+    ///     _ => { panic!("expected conflicting field"); }
+    /// }
+    ///
+    /// assert_eq!(rec.measurements().len(), 3);
+    ///
+    /// // 1.2s and 1.7s both get written as second 1
+    /// let mut rec = Record::new("org", "bucket")
+    ///     .precision(Precision::Seconds);
+    ///
+    /// rec.measurement("cpu").field("user", 0.5).timestamp((1200, Precision::Milliseconds));
+    /// rec.measurement("cpu").field("system", 0.25).timestamp((1700, Precision::Milliseconds));
+    ///
+    /// rec.compact().unwrap();
+    ///
+    /// assert_eq!(rec.to_string().lines().count(), 2);
+    ///
+    /// rec.measurement("cpu").field("user", 0.75).timestamp((1900, Precision::Milliseconds));
+    ///
+    /// assert!(rec.compact().is_err());
+    /// ```
+    pub fn compact(&mut self) -> InfluxResult<()>
+    {
+        let mut series    = HashMap::new();
+        let mut compacted = Vec::<Measurement>::with_capacity(self.measurements.len());
+        let mut errors    = Vec::new();
+
+        for (index, measurement) in self.measurements.iter().enumerate()
+        {
+            let timestamp = match &measurement.timestamp
+            {
+                Some(ts) => { Some(self.precision.to_timestamp(ts, self.rounding)?) }
+                None     => { None }
+            };
+
+            let key = (measurement.series_key(&self.default_tags)?, timestamp);
+
+            if let Some(&target) = series.get(&key)
+            {
+                let merged: &mut Measurement = &mut compacted[target];
+
+                for (field, value) in measurement.fields.iter()
+                {
+                    match merged.fields.get(field)
+                    {
+                        Some(existing) if ! existing.is_identical(value) => {
+                            errors.push(ValidationError::new(index, Some(field), ValidationKind::ConflictingField));
+                        }
+
+                        Some(_) => {}
+                        None    => { merged.fields.insert(field.clone(), value.clone()); }
+                    }
+                }
+            }
+            else
+            {
+                series.insert(key, compacted.len());
+                compacted.push(measurement.clone());
+            }
+        }
+
+        if ! errors.is_empty() {
+            return Err(InfluxError::Invalid(errors));
+        }
+
+        if compacted.len() < self.measurements.len() {
+            debug!("Compacted {} measurements into {}", self.measurements.len(), compacted.len());
+        }
+
+        self.measurements = compacted;
+
+        Ok(())
+    }
+
+    /// Stream all measurements as newline terminated line protocol into the writer. This is what gets sent to InfluxDB
+    /// and avoids building intermediate strings, so writing straight into a file or compressor is cheap.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    ///
+    /// let mut rec = Record::new("org", "bucket")
+    ///     .server_timestamps();
+    ///
+    /// rec.measurement("sensor1").tag("floor", "second").field("temp", 123);
+    /// rec.measurement("sensor2").tag("floor", "first").field("temp", 1.5);
+    ///
+    /// let mut buffer = Vec::new();
+    ///
+    /// rec.write_lines(&mut buffer).unwrap();
+    ///
+    /// assert_eq!(buffer, b"sensor1,floor=second temp=123i\nsensor2,floor=first temp=1.5\n");
+    /// ```
+    pub fn write_lines<W: Write>(&self, writer: &mut W) -> InfluxResult<()>
+    {
+        self.write_lines_tagged(writer, &self.default_tags)
+    }
 }
 
 
 impl Record
 {
-    pub(crate) fn to_lines(&self) -> Vec<String>
+    fn stamp(&self) -> Option<DateTime>
     {
-        let mut lines = Vec::new();
+        if self.server_timestamps { None } else { Some(Utc::now()) }
+    }
+
+    /// Copy of this record with the given default tags added to its own ones, which take precedence
+    pub(crate) fn with_default_tags(&self, defaults: &Tags) -> Self
+    {
+        let mut this = self.clone();
+
+        this.default_tags = self.merged_default_tags(defaults);
+
+        this
+    }
+
+    fn merged_default_tags(&self, defaults: &Tags) -> Tags
+    {
+        let mut tags = defaults.clone();
+
+        tags.extend(self.default_tags.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        tags
+    }
+
+    /// Everything records need to share for their measurements to be written in the same request
+    pub(crate) fn batch_key(&self) -> (Target, Target, Precision, Rounding)
+    {
+        (self.org.clone(), self.bucket.clone(), self.precision.clone(), self.rounding)
+    }
 
-        for measurement in self.measurements.iter() {
-            lines.push(measurement.to_line(&self.precision));
+    /// Move all measurements of the other record into this one, with its default tags merged into them. Both are
+    /// expected to share the same [batch key](#method.batch_key).
+    pub(crate) fn absorb(&mut self, other: Record)
+    {
+        if other.default_tags.is_empty() {
+            self.measurements.extend(other.measurements);
+        } else {
+            self.measurements.extend(other.measurements.iter().map(|m| m.with_tags(&other.default_tags)));
         }
+    }
+
+    /// Split into consecutive chunks of at most the given number of lines and uncompressed bytes each, counting the
+    /// given default tags merged in. A single line exceeding the byte limit still makes up a chunk of its own. Every
+    /// chunk comes with the indices of the measurements it covers.
+    pub(crate) fn split(&self, max_lines: Option<usize>, max_bytes: Option<usize>, defaults: &Tags)
+        -> InfluxResult<Vec<(Range<usize>, Record)>>
+    {
+        let max_lines = max_lines.unwrap_or(usize::MAX).max(1);
+        let max_bytes = max_bytes.unwrap_or(usize::MAX);
+
+        let defaults = self.merged_default_tags(defaults);
+
+        let mut chunks = Vec::new();
+        let mut buffer = Vec::new();
+
+        let mut start = 0;
+        let mut bytes = 0;
+
+        for (index, measurement) in self.measurements.iter().enumerate()
+        {
+            let length = if max_bytes == usize::MAX { 0 } else {
+                buffer.clear();
+                measurement.write_line_tagged(&mut buffer, &self.precision, self.rounding, &defaults)?;
+                buffer.len() + 1
+            };
+
+            if index > start && (index - start >= max_lines || bytes + length > max_bytes)
+            {
+                chunks.push((start..index, self.slice(start..index)));
+
+                start = index;
+                bytes = 0;
+            }
+
+            bytes += length;
+        }
+
+        chunks.push((start..self.measurements.len(), self.slice(start..self.measurements.len())));
+
+        Ok(chunks)
+    }
+
+    /// Copy of this record holding only the given range of measurements
+    fn slice(&self, range: Range<usize>) -> Self
+    {
+        Self {
+            org:          self.org.clone(),
+            bucket:       self.bucket.clone(),
+            precision:    self.precision.clone(),
+            rounding:     self.rounding,
+            measurements: self.measurements[range].to_vec(),
+            default_tags: self.default_tags.clone(),
 
-        lines
+            server_timestamps: self.server_timestamps,
+        }
     }
 
-    pub(crate) fn to_line_buffer(&self) -> String
+    /// Stream all measurements with the given default tags, instead of the ones of this record
+    fn write_lines_tagged<W: Write>(&self, writer: &mut W, defaults: &Tags) -> InfluxResult<()>
     {
-        self.to_lines()
-            .join("\n")
+        for measurement in self.measurements.iter()
+        {
+            measurement.write_line_tagged(writer, &self.precision, self.rounding, defaults)?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
     }
 
-    pub(crate) fn to_write_request(&self, mut builder: ReqwRequestBuilder) -> InfluxResult<ReqwRequestBuilder>
+    /// Line protocol to send, compressed as configured, with the client wide default tags merged below the ones of this
+    /// record. Tells whether the body got gzipped.
+    pub(crate) fn to_write_body(&self, defaults: &Tags, compression: Compression) -> InfluxResult<(Vec<u8>, bool)>
     {
-        // buffer compression
-        let mut gzipenc = FlateGzipBuilder::new()
-            .write(Vec::new(), FlateLevel::default());
+        let defaults = self.merged_default_tags(defaults);
+
+        match compression
+        {
+            Compression::Off => {
+                let mut buffer = Vec::new();
+
+                self.write_lines_tagged(&mut buffer, &defaults)?;
+
+                Ok((buffer, false))
+            }
+
+            Compression::Gzip(level) => {
+                let mut gzipenc = FlateGzipBuilder::new()
+                    .write(Vec::new(), FlateLevel::new(level.min(9)));
+
+                {
+                    let mut writer = BufWriter::new(&mut gzipenc);
+
+                    self.write_lines_tagged(&mut writer, &defaults)?;
+
+                    writer.flush()?;
+                }
+
+                Ok((gzipenc.finish()?, true))
+            }
+
+            Compression::GzipAbove{bytes, level} => {
+                let mut buffer = Vec::new();
+
+                self.write_lines_tagged(&mut buffer, &defaults)?;
 
-        gzipenc.write_all(self.to_line_buffer().as_bytes())?;
+                if buffer.len() <= bytes {
+                    return Ok((buffer, false));
+                }
 
-        let buffer = gzipenc.finish()?;
+                let mut gzipenc = FlateGzipBuilder::new()
+                    .write(Vec::with_capacity(buffer.len() / 4), FlateLevel::new(level.min(9)));
 
-        // headers and query path
-        builder = builder.header("Content-Encoding", "gzip");
+                gzipenc.write_all(&buffer)?;
 
-        builder = builder.query(&[
-            ("org",       &self.org),
-            ("bucket",    &self.bucket),
-            ("precision", &self.precision.to_string()),
-        ]);
+                Ok((gzipenc.finish()?, true))
+            }
+        }
+    }
 
-        // buffer body
-        Ok(builder.body(buffer))
+    /// Query parameters of the write request
+    pub(crate) fn to_write_query(&self) -> [(&'static str, String); 3]
+    {
+        [
+            (if self.org.is_id() { "orgID" } else { "org" }, self.org.as_str().to_owned()),
+            ("bucket",    self.bucket.as_str().to_owned()),
+            ("precision", self.precision.to_string()),
+        ]
     }
 }
 
@@ -106,7 +636,7 @@ impl std::fmt::Display for Record
     {
         let lines = self.measurements.iter()
             .map(|m| {
-                let tags = m.tags.iter()
+                let tags = m.tags_with(&self.default_tags)
                     .map(|(k, v)| format!("{}:{}", k, v))
                     .collect::<Vec<String>>()
                     .join(" ");
@@ -116,7 +646,11 @@ impl std::fmt::Display for Record
                     .collect::<Vec<String>>()
                     .join(" ");
 
-                format!("\tmeasurement={} {} {} {}", m.name, tags, fields, m.timestamp)
+                let timestamp = m.timestamp
+                    .map(|ts| ts.to_string())
+                    .unwrap_or_else(|| "<server time>".to_owned());
+
+                format!("\tmeasurement={} {} {} {}", m.name, tags, fields, timestamp)
             })
             .collect::<Vec<String>>()
             .join("\n");
@@ -125,3 +659,177 @@ impl std::fmt::Display for Record
         write!(f, "Record(org={}, bucket={}, precision={})\n{}", self.org, self.bucket, self.precision, lines)
     }
 }
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::FlateGzipDecoder;
+
+    use std::io::Read;
+
+
+    fn record() -> Record
+    {
+        let mut rec = Record::new("org", "bucket").server_timestamps();
+
+        for idx in 0..16_i64 {
+            rec.measurement("cpu").tag("host", "alpha").field("idx", idx);
+        }
+
+        rec
+    }
+
+
+    fn body(compression: Compression) -> (Vec<u8>, bool)
+    {
+        record().to_write_body(&Tags::new(), compression).unwrap()
+    }
+
+
+    fn gunzip(body: &[u8]) -> Vec<u8>
+    {
+        let mut decoded = Vec::new();
+
+        FlateGzipDecoder::new(body).read_to_end(&mut decoded).unwrap();
+
+        decoded
+    }
+
+
+    fn compacted(mut rec: Record) -> InfluxResult<String>
+    {
+        rec.compact()?;
+
+        let mut lines = Vec::new();
+        rec.write_lines(&mut lines)?;
+
+        Ok(String::from_utf8(lines).unwrap())
+    }
+
+
+    #[test]
+    fn rejects_serialized_values_without_fields()
+    {
+        #[derive(serde::Serialize)]
+        struct Reading
+        {
+            device: String,
+            temp:   Option<f64>,
+        }
+
+        let mapping = SerdeMapping::new().tags(&["device"]);
+        let reading = Reading {device: "dev-7".to_owned(), temp: None};
+
+        let mut rec = Record::new("org", "bucket");
+
+        assert!(matches!(rec.push_serialized("reading", &(), &mapping), Err(InfluxError::Invalid(_))));
+        assert!(matches!(rec.push_serialized("reading", &reading, &mapping), Err(InfluxError::Invalid(_))));
+        assert!(rec.measurements().is_empty());
+
+        let reading = Reading {temp: Some(21.5), ..reading};
+
+        rec.push_serialized("reading", &reading, &mapping).unwrap();
+
+        assert_eq!(rec.measurements().len(), 1);
+    }
+
+
+    #[test]
+    fn compacts_identical_nan_fields()
+    {
+        let mut rec = Record::new("org", "bucket").server_timestamps();
+
+        rec.measurement("cpu").field("load", f64::NAN).field("user", 0.5);
+        rec.measurement("cpu").field("load", f64::NAN).field("system", 0.25);
+
+        assert_eq!(compacted(rec).unwrap(), "cpu load=NaN,system=0.25,user=0.5\n");
+
+        let mut rec = Record::new("org", "bucket").server_timestamps();
+
+        rec.measurement("cpu").field("load", 0.0);
+        rec.measurement("cpu").field("load", -0.0);
+
+        assert!(compacted(rec).is_err());
+    }
+
+
+    #[test]
+    fn compacts_with_default_tags()
+    {
+        let mut rec = Record::new("org", "bucket")
+            .server_timestamps()
+            .default_tag("host", "alpha");
+
+        rec.measurement("cpu").tag("host", "alpha").field("user", 0.5);
+        rec.measurement("cpu").field("system", 0.25);
+        rec.measurement("cpu").tag("host", "beta").field("user", 0.75);
+
+        assert_eq!(compacted(rec).unwrap(), "cpu,host=alpha system=0.25,user=0.5\ncpu,host=beta user=0.75\n");
+    }
+
+
+    #[test]
+    fn compacts_timestamps_equal_at_record_precision()
+    {
+        let record = |rounding| {
+            let mut rec = Record::new("org", "bucket")
+                .precision(Precision::Seconds)
+                .rounding(rounding);
+
+            rec.measurement("cpu").field("user", 0.5).timestamp((1200, Precision::Milliseconds));
+            rec.measurement("cpu").field("system", 0.25).timestamp((1700, Precision::Milliseconds));
+            rec
+        };
+
+        assert_eq!(compacted(record(Rounding::Truncate)).unwrap(), "cpu system=0.25,user=0.5 1\n");
+        assert_eq!(compacted(record(Rounding::Round)).unwrap(), "cpu user=0.5 1\ncpu system=0.25 2\n");
+    }
+
+
+    #[test]
+    fn sends_plain_body_with_compression_off()
+    {
+        let mut lines = Vec::new();
+
+        record().write_lines(&mut lines).unwrap();
+
+        assert_eq!(body(Compression::Off), (lines, false));
+    }
+
+
+    #[test]
+    fn gzips_every_body_at_any_level()
+    {
+        let (plain, _) = body(Compression::Off);
+
+        for level in [0, 6, 9, 12]
+        {
+            let (gzipped, flag) = body(Compression::Gzip(level));
+
+            assert!(flag, "level {}", level);
+            assert_eq!(gunzip(&gzipped), plain, "level {}", level);
+        }
+    }
+
+
+    #[test]
+    fn gzips_bodies_above_threshold_only()
+    {
+        let (plain, _) = body(Compression::Off);
+
+        // body below and exactly at the threshold
+        for bytes in [plain.len() + 1, plain.len()]
+        {
+            assert_eq!(body(Compression::GzipAbove {bytes, level: 6}), (plain.clone(), false), "{} bytes", bytes);
+        }
+
+        // body above it
+        let (gzipped, flag) = body(Compression::GzipAbove {bytes: plain.len() - 1, level: 6});
+
+        assert!(flag);
+        assert_eq!(gunzip(&gzipped), plain);
+    }
+}
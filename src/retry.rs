@@ -0,0 +1,256 @@
+//!
+//! Retrying of Writes Failing for Transient Reasons
+//!
+use crate::InfluxError;
+
+use std::hash::Hasher;
+use std::hash::BuildHasher;
+use std::collections::hash_map::RandomState;
+
+use std::time::Duration;
+
+
+/// How often and how long to retry writes failing for transient reasons, before the record is given up on and
+/// backlogged. Transient are exceeded quotas (429), an unready server (503), timeouts and refused connections.
+///
+/// Waits double with every attempt, starting at the initial backoff up to the maximum one, with a random part of up
+/// to half of it to keep clients from retrying in lockstep. A delay asked for by the server, in the reply or in its
+/// `Retry-After` header, is waited at least. Retrying stops once the attempts are used up or the next wait would
+/// exceed the maximum total time.
+///
+/// ## Examples
+///
+/// ```rust,no_run
+/// use influxc::Client;
+/// use influxc::Record;
+/// use influxc::Credentials;
+/// use influxc::RetryPolicy;
+///
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(3)
+///     .backoff(Duration::from_millis(50), Duration::from_secs(1))
+///     .max_elapsed(Duration::from_secs(10))
+///     .jitter(false);
+///
+/// let client = Client::build("http://127.0.0.1:8086".into(), Credentials::from_token("TOKEN"))
+///     .retry(policy)
+///     .finish()
+///     .expect("Failed to build client");
+///
+/// let mut rec = Record::new("org", "bucket");
+/// rec.measurement("cpu").field("load", 0.5);
+///
+/// // waits 50ms and then 100ms should the server be unavailable, before giving up on the record
+/// client.write(&rec).expect("Failed to write");
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryPolicy
+{
+    max_attempts: u32,
+    initial:      Duration,
+    maximum:      Duration,
+    max_elapsed:  Option<Duration>,
+    jitter:       bool,
+}
+
+
+impl RetryPolicy
+{
+    /// Attempt writes up to the given number of times in total, waiting half a second initially and up to 30 seconds
+    /// in between, with jitter and without limit on the total time.
+    pub fn new(max_attempts: u32) -> Self
+    {
+        Self {
+            max_attempts,
+
+            initial:     Duration::from_millis(500),
+            maximum:     Duration::from_secs(30),
+            max_elapsed: None,
+            jitter:      true,
+        }
+    }
+
+    /// Wait the initial backoff after the first attempt, doubling with every further one up to the maximum
+    pub fn backoff(mut self, initial: Duration, maximum: Duration) -> Self
+    {
+        self.initial = initial;
+        self.maximum = maximum.max(initial);
+        self
+    }
+
+    /// Give up once the next wait would exceed this much time since the first attempt
+    pub fn max_elapsed(mut self, max_elapsed: Duration) -> Self
+    {
+        self.max_elapsed = Some(max_elapsed); self
+    }
+
+    /// Whether to randomize waits
+    pub fn jitter(mut self, jitter: bool) -> Self
+    {
+        self.jitter = jitter; self
+    }
+
+    /// Time to wait before the next attempt, given the attempts done so far, the error of the last one and the time
+    /// passed since the first. None if the write is not to be retried.
+    pub(crate) fn next_delay(&self, attempts: u32, error: &InfluxError, elapsed: Duration) -> Option<Duration>
+    {
+        if attempts >= self.max_attempts || ! is_transient(error) {
+            return None;
+        }
+
+        let exponent = attempts.saturating_sub(1).min(31);
+        let mut delay = self.initial.saturating_mul(1 << exponent).min(self.maximum);
+
+        if self.jitter {
+            delay = delay / 2 + delay.mul_f64(random() / 2.0);
+        }
+
+        if let Some(requested) = server_delay(error) {
+            delay = delay.max(requested);
+        }
+
+        match self.max_elapsed
+        {
+            Some(max_elapsed) if elapsed + delay > max_elapsed => { None }
+            _                                                  => { Some(delay) }
+        }
+    }
+}
+
+
+/// Errors worth another attempt, as they depend on the state of the server or network rather than on the record
+pub(crate) fn is_transient(error: &InfluxError) -> bool
+{
+    match error
+    {
+        InfluxError::WriteOverquota(_) | InfluxError::WriteUnready(_) => { true }
+        InfluxError::Reqwest(e)                                        => { e.is_timeout() || e.is_connect() }
+
+        _ => { false }
+    }
+}
+
+
+fn server_delay(error: &InfluxError) -> Option<Duration>
+{
+    match error
+    {
+        InfluxError::WriteOverquota(e) | InfluxError::WriteUnready(e) => { Some(e.delay()) }
+
+        _ => { None }
+    }
+}
+
+
+/// Random number between 0 and 1. Jitter does not need more than the randomly seeded std hasher offers.
+fn random() -> f64
+{
+    let mut hasher = RandomState::new().build_hasher();
+
+    hasher.write_u64(0);
+
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::ApiDelayError;
+    use crate::ApiGenericError;
+
+
+    fn unready(delay: i64) -> InfluxError
+    {
+        let mut error = ApiDelayError::default();
+
+        error.retry_after(delay);
+
+        InfluxError::WriteUnready(error)
+    }
+
+
+    fn delays(policy: &RetryPolicy, error: &InfluxError) -> Vec<Option<Duration>>
+    {
+        (1..=6).map(|attempts| policy.next_delay(attempts, error, Duration::ZERO)).collect()
+    }
+
+
+    #[test]
+    fn backs_off_up_to_maximum()
+    {
+        let policy = RetryPolicy::new(6)
+            .backoff(Duration::from_millis(100), Duration::from_millis(500))
+            .jitter(false);
+
+        assert_eq!(delays(&policy, &unready(0)), vec![
+            Some(Duration::from_millis(100)),
+            Some(Duration::from_millis(200)),
+            Some(Duration::from_millis(400)),
+            Some(Duration::from_millis(500)),
+            Some(Duration::from_millis(500)),
+            None,
+        ]);
+    }
+
+
+    #[test]
+    fn jitters_within_upper_half()
+    {
+        let policy = RetryPolicy::new(6)
+            .backoff(Duration::from_millis(100), Duration::from_millis(500));
+
+        for (attempts, expected) in [(1, 100), (3, 400), (5, 500)]
+        {
+            let delay = policy.next_delay(attempts, &unready(0), Duration::ZERO).unwrap();
+
+            assert!(delay >= Duration::from_millis(expected / 2) && delay <= Duration::from_millis(expected));
+        }
+    }
+
+
+    #[test]
+    fn waits_as_long_as_server_asks()
+    {
+        let policy = RetryPolicy::new(6)
+            .backoff(Duration::from_millis(100), Duration::from_millis(500))
+            .jitter(false);
+
+        let delays = delays(&policy, &unready(2));
+
+        assert!(delays[..5].iter().all(|delay| *delay == Some(Duration::from_secs(2))));
+
+        let quota = InfluxError::WriteOverquota(ApiDelayError::default());
+
+        assert_eq!(policy.next_delay(1, &quota, Duration::ZERO), Some(Duration::from_millis(100)));
+    }
+
+
+    #[test]
+    fn gives_up_past_max_elapsed()
+    {
+        let policy = RetryPolicy::new(6)
+            .backoff(Duration::from_millis(100), Duration::from_millis(500))
+            .max_elapsed(Duration::from_secs(1))
+            .jitter(false);
+
+        assert_eq!(policy.next_delay(3, &unready(0), Duration::from_millis(600)), Some(Duration::from_millis(400)));
+        assert_eq!(policy.next_delay(3, &unready(0), Duration::from_millis(700)), None);
+        assert_eq!(policy.next_delay(1, &unready(2), Duration::ZERO), None);
+    }
+
+
+    #[test]
+    fn retries_transient_errors_only()
+    {
+        let policy = RetryPolicy::new(6).jitter(false);
+
+        let unauthorized = InfluxError::WriteUnauthorized(ApiGenericError::new("unauthorized", String::new()));
+
+        assert_eq!(policy.next_delay(1, &unauthorized, Duration::ZERO), None);
+        assert_eq!(policy.next_delay(1, &InfluxError::from("failed"), Duration::ZERO), None);
+    }
+}
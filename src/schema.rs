@@ -0,0 +1,302 @@
+//!
+//! Client Side Tracking of Field Types
+//!
+//! InfluxDB rejects a whole write if a field changes its type within a measurement, so these conflicts are best caught
+//! before the record leaves the process. Only the types written through the tracker are known to it, so a freshly
+//! started process learns them anew.
+//!
+use crate::Value;
+use crate::Record;
+use crate::Target;
+use crate::ValueType;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+use crate::FieldConflictError;
+
+use std::collections::HashMap;
+
+
+/// How the [SchemaTracker](struct.SchemaTracker.html) deals with a field written with a different type than before
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPolicy
+{
+    /// Reject the whole record on any conflict
+    Reject,
+
+    /// Convert integers into floats where the field is known as float or is one elsewhere in the same record, reject
+    /// any other conflict
+    Coerce,
+}
+
+
+/// Remembers the type of every field written per org, bucket and measurement, and checks records against it.
+#[derive(Debug)]
+pub struct SchemaTracker
+{
+    policy: ConflictPolicy,
+    types:  HashMap<FieldId, ValueType>,
+}
+
+
+/// Org, bucket, measurement and field
+type FieldId = (Target, Target, String, String);
+
+
+impl SchemaTracker
+{
+    /// Construct an empty tracker handling conflicts according to the given policy
+    pub fn new(policy: ConflictPolicy) -> Self
+    {
+        Self {policy, types: HashMap::new()}
+    }
+
+    /// Check the record against the known field types, coercing values where the policy allows for it. Types of new
+    /// fields get remembered, unless the record is rejected.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use influxc::Record;
+    /// use influxc::Precision;
+    /// use influxc::ValueType;
+    /// use influxc::InfluxError;
+    /// use influxc::SchemaTracker;
+    /// use influxc::ConflictPolicy;
+    ///
+    /// let mut schema = SchemaTracker::new(ConflictPolicy::Coerce);
+    ///
+    /// let mut rec = Record::new("org", "bucket");
+    /// rec.measurement("sensor").field("temp", 21.5);
+    /// rec.measurement("sensor").field("temp", 22);
+    ///
+    /// schema.check(&mut rec).unwrap();
+    ///
+    /// assert_eq!(rec.measurements()[1].to_line(&Precision::Seconds).unwrap().split(' ').nth(1), Some("temp=22"));
+    ///
+    /// let mut rec = Record::new("org", "bucket");
+    /// rec.measurement("sensor").field("temp", "hot".to_owned());
+    ///
+    /// match schema.check(&mut rec) {
+    ///     Err(InfluxError::FieldConflict(conflicts)) => {
+    ///         assert_eq!(conflicts[0].field(), "temp");
+    ///         assert_eq!(conflicts[0].expected(), ValueType::Float);
+    ///         assert_eq!(conflicts[0].found(), ValueType::String);
+    ///     }
+    ///     _ => { panic!("expected field conflict"); }
+    /// }
+    /// ```
+    pub fn check(&mut self, record: &mut Record) -> InfluxResult<()>
+    {
+        let coercions = self.inspect(record)?;
+
+        Self::coerce(record, &coercions);
+
+        self.learn(record);
+
+        Ok(())
+    }
+}
+
+
+impl SchemaTracker
+{
+    /// Find conflicts without modifying anything. On success returns the measurement index and field key of every
+    /// value in need of coercion to float.
+    pub(crate) fn inspect(&self, record: &Record) -> InfluxResult<Vec<(usize, String)>>
+    {
+        let mut seen      = HashMap::new();
+        let mut coercions = Vec::new();
+        let mut conflicts = Vec::new();
+
+        // fields new to the tracker take the type of their first value in the record, widened to float by any later
+        // float value as far as the policy allows, so the order of the values does not matter
+        for msrmt in record.measurements.iter()
+        {
+            for (field, value) in msrmt.fields.iter()
+            {
+                let id    = field_id(record, &msrmt.name, field);
+                let found = value.value_type();
+
+                if ! self.types.contains_key(&id)
+                {
+                    let expected = seen.entry(id).or_insert(found);
+
+                    if self.coercible(found, *expected) {
+                        *expected = ValueType::Float;
+                    }
+                }
+            }
+        }
+
+        for (index, msrmt) in record.measurements.iter().enumerate()
+        {
+            for (field, value) in msrmt.fields.iter()
+            {
+                let id    = field_id(record, &msrmt.name, field);
+                let found = value.value_type();
+
+                let expected = match self.types.get(&id).or_else(|| seen.get(&id))
+                {
+                    Some(expected) => { *expected }
+                    None           => { continue; }
+                };
+
+                if expected == found {
+                    continue;
+                }
+
+                if self.coercible(expected, found) {
+                    coercions.push((index, field.clone()));
+                } else {
+                    conflicts.push(FieldConflictError::new(index, field, expected, found));
+                }
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(coercions)
+        } else {
+            Err(InfluxError::FieldConflict(conflicts))
+        }
+    }
+
+    /// Whether a value of the found type may be converted into the expected one
+    fn coercible(&self, expected: ValueType, found: ValueType) -> bool
+    {
+        self.policy == ConflictPolicy::Coerce
+            && expected == ValueType::Float
+            && (found == ValueType::Integer || found == ValueType::Unsigned)
+    }
+
+    /// Convert the values found in need of coercion by `inspect` into floats
+    pub(crate) fn coerce(record: &mut Record, coercions: &[(usize, String)])
+    {
+        for (index, field) in coercions.iter()
+        {
+            if let Some(value) = record.measurements[*index].fields.get_mut(field)
+            {
+                match *value
+                {
+                    Value::Integer(v)  => { *value = Value::Float(v as f64); }
+                    Value::Unsigned(v) => { *value = Value::Float(v as f64); }
+                    _                  => {}
+                }
+            }
+        }
+    }
+
+    /// Remember the field types of an accepted record
+    pub(crate) fn learn(&mut self, record: &Record)
+    {
+        for msrmt in record.measurements.iter()
+        {
+            for (field, value) in msrmt.fields.iter()
+            {
+                self.types.entry(field_id(record, &msrmt.name, field))
+                    .or_insert_with(|| value.value_type());
+            }
+        }
+    }
+}
+
+
+fn field_id(record: &Record, measurement: &str, field: &str) -> FieldId
+{
+    (record.org.clone(), record.bucket.clone(), measurement.to_owned(), field.to_owned())
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+
+    fn record(values: &[Value]) -> Record
+    {
+        let mut rec = Record::new("org", "bucket");
+
+        for value in values.iter() {
+            rec.measurement("sensor").field("temp", value.clone());
+        }
+
+        rec
+    }
+
+
+    fn types(rec: &Record) -> Vec<ValueType>
+    {
+        rec.measurements().iter().map(|msrmt| msrmt.fields()["temp"].value_type()).collect()
+    }
+
+
+    fn conflicts(result: InfluxResult<()>) -> Vec<(usize, ValueType, ValueType)>
+    {
+        match result
+        {
+            Err(InfluxError::FieldConflict(conflicts)) => {
+                conflicts.iter().map(|c| (c.index(), c.expected(), c.found())).collect()
+            }
+
+            other => { panic!("Expected field conflict, got {:?}", other); }
+        }
+    }
+
+
+    #[test]
+    fn coerces_regardless_of_order()
+    {
+        let mut schema = SchemaTracker::new(ConflictPolicy::Coerce);
+
+        let mut rec = record(&[Value::Integer(21), Value::Float(21.5), Value::Unsigned(22)]);
+
+        schema.check(&mut rec).unwrap();
+
+        assert_eq!(types(&rec), vec![ValueType::Float; 3]);
+
+        let mut schema = SchemaTracker::new(ConflictPolicy::Coerce);
+
+        let mut rec = record(&[Value::Float(21.5), Value::Integer(21)]);
+
+        schema.check(&mut rec).unwrap();
+
+        assert_eq!(types(&rec), vec![ValueType::Float; 2]);
+    }
+
+
+    #[test]
+    fn rejects_mixed_types_without_coercion()
+    {
+        let mut schema = SchemaTracker::new(ConflictPolicy::Reject);
+
+        let result = schema.check(&mut record(&[Value::Integer(21), Value::Float(21.5)]));
+
+        assert_eq!(conflicts(result), vec![(1, ValueType::Integer, ValueType::Float)]);
+
+        let mut schema = SchemaTracker::new(ConflictPolicy::Coerce);
+
+        let result = schema.check(&mut record(&[Value::Integer(21), Value::Unsigned(22)]));
+
+        assert_eq!(conflicts(result), vec![(1, ValueType::Integer, ValueType::Unsigned)]);
+    }
+
+
+    #[test]
+    fn keeps_known_types()
+    {
+        let mut schema = SchemaTracker::new(ConflictPolicy::Coerce);
+
+        schema.check(&mut record(&[Value::Integer(21)])).unwrap();
+
+        let result = schema.check(&mut record(&[Value::Float(21.5), Value::Integer(22)]));
+
+        assert_eq!(conflicts(result), vec![(0, ValueType::Integer, ValueType::Float)]);
+
+        let mut rec = record(&[Value::Integer(22)]);
+
+        schema.check(&mut rec).unwrap();
+
+        assert_eq!(types(&rec), vec![ValueType::Integer]);
+    }
+}
@@ -0,0 +1,383 @@
+//!
+//! Serde Serializer Mapping Serializable Types onto Measurements
+//!
+//! Structs and maps get flattened into a list of keys and primitive values, nested keys joined by a separator, which
+//! are then distributed onto tags, fields and the timestamp of a measurement according to a
+//! [SerdeMapping](struct.SerdeMapping.html).
+//!
+use crate::DateTime;
+use crate::Value;
+use crate::Precision;
+use crate::Measurement;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use serde::Serialize;
+
+use serde::ser;
+use serde::ser::Impossible;
+
+use std::fmt::Display;
+use std::convert::TryFrom;
+
+
+/// Rules by which the keys of a serialized value are distributed onto a measurement. By default everything becomes a
+/// field and nested keys are joined by an underscore.
+#[derive(Debug, Clone)]
+pub struct SerdeMapping
+{
+    tags:      TagRule,
+    timestamp: Option<String>,
+    separator: String,
+}
+
+
+#[derive(Debug, Clone)]
+enum TagRule
+{
+    Keys(Vec<String>),
+    Strings,
+}
+
+
+impl SerdeMapping
+{
+    /// Construct mapping turning everything into fields
+    pub fn new() -> Self
+    {
+        Self {
+            tags:      TagRule::Keys(Vec::new()),
+            timestamp: None,
+            separator: "_".to_owned(),
+        }
+    }
+
+    /// Turn the values of the given (flattened) keys into tags
+    pub fn tags<S: AsRef<str>>(mut self, keys: &[S]) -> Self
+    {
+        self.tags = TagRule::Keys(keys.iter().map(|k| k.as_ref().to_owned()).collect()); self
+    }
+
+    /// Turn all string values into tags
+    pub fn string_tags(mut self) -> Self
+    {
+        self.tags = TagRule::Strings; self
+    }
+
+    /// Take the timestamp from the given (flattened) key. Its value either has to be an RFC 3339 string, as chrono
+    /// serializes its datetimes, or an integer in units of the record precision.
+    pub fn timestamp(mut self, key: &str) -> Self
+    {
+        self.timestamp = Some(key.to_owned()); self
+    }
+
+    /// Join keys of nested structs and maps with the given separator instead of an underscore
+    pub fn separator(mut self, separator: &str) -> Self
+    {
+        self.separator = separator.to_owned(); self
+    }
+}
+
+
+impl SerdeMapping
+{
+    /// Serialize the value and distribute its flattened keys onto the measurement
+    pub(crate) fn fill<T>(&self, measurement: &mut Measurement, value: &T, precision: &Precision) -> InfluxResult<()>
+        where T: Serialize + ?Sized
+    {
+        let mut leaves = Vec::new();
+
+        value.serialize(Flattener {leaves: &mut leaves, prefix: String::new(), separator: &self.separator})?;
+
+        for (key, value) in leaves.into_iter()
+        {
+            if self.timestamp.as_ref() == Some(&key)
+            {
+                measurement.timestamp(timestamp_from(&key, value, precision)?);
+                continue;
+            }
+
+            let is_tag = match &self.tags
+            {
+                TagRule::Keys(keys) => { keys.contains(&key) }
+                TagRule::Strings    => { matches!(value, Value::String(_)) }
+            };
+
+            if is_tag {
+                measurement.tag(&key, &value.to_string());
+            } else {
+                measurement.field(&key, value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+fn timestamp_from(key: &str, value: Value, precision: &Precision) -> InfluxResult<DateTime>
+{
+    match value
+    {
+        Value::String(s) => {
+            chrono::DateTime::parse_from_rfc3339(&s)
+                .map(|dt| dt.with_timezone(&chrono::Utc))
+                .map_err(|e| format!("Invalid RFC 3339 timestamp in '{}': {}", key, e).into())
+        }
+
+        Value::Integer(v) => { precision.to_datetime(v) }
+
+        Value::Unsigned(v) => {
+            let v = i64::try_from(v)
+                .map_err(|_| InfluxError::from(format!("Timestamp in '{}' out of range", key)))?;
+
+            precision.to_datetime(v)
+        }
+
+        other => { Err(format!("Timestamp in '{}' has to be a string or integer, found {:?}", key, other.value_type()).into()) }
+    }
+}
+
+
+impl ser::Error for InfluxError
+{
+    fn custom<T: Display>(msg: T) -> Self
+    {
+        InfluxError::Error(msg.to_string())
+    }
+}
+
+
+/// Collects the primitive values of a serialized value with their keys, joining nested keys by the separator. Only
+/// structs and maps are accepted at the top, sequences are not supported at all.
+struct Flattener<'a>
+{
+    leaves:    &'a mut Vec<(String, Value)>,
+    prefix:    String,
+    separator: &'a str,
+}
+
+
+impl<'a> Flattener<'a>
+{
+    fn leaf(self, value: Value) -> InfluxResult<()>
+    {
+        if self.prefix.is_empty() {
+            return Err("Only structs and maps can be serialized into a measurement".into());
+        }
+
+        self.leaves.push((self.prefix, value)); Ok(())
+    }
+
+    fn nested(&mut self, key: &str) -> Flattener<'_>
+    {
+        let prefix = if self.prefix.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}{}{}", self.prefix, self.separator, key)
+        };
+
+        Flattener {leaves: &mut *self.leaves, prefix, separator: self.separator}
+    }
+
+    fn unsupported(&self, what: &str) -> InfluxError
+    {
+        format!("Unable to map {} onto a measurement at '{}'", what, self.prefix).into()
+    }
+}
+
+
+impl<'a> ser::Serializer for Flattener<'a>
+{
+    type Ok    = ();
+    type Error = InfluxError;
+
+    type SerializeSeq           = Impossible<(), InfluxError>;
+    type SerializeTuple         = Impossible<(), InfluxError>;
+    type SerializeTupleStruct   = Impossible<(), InfluxError>;
+    type SerializeTupleVariant  = Impossible<(), InfluxError>;
+    type SerializeMap           = FlatMap<'a>;
+    type SerializeStruct        = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> InfluxResult<()> { self.leaf(Value::Boolean(v)) }
+
+    fn serialize_i8(self,  v: i8)  -> InfluxResult<()> { self.leaf(Value::Integer(v.into())) }
+    fn serialize_i16(self, v: i16) -> InfluxResult<()> { self.leaf(Value::Integer(v.into())) }
+    fn serialize_i32(self, v: i32) -> InfluxResult<()> { self.leaf(Value::Integer(v.into())) }
+    fn serialize_i64(self, v: i64) -> InfluxResult<()> { self.leaf(Value::Integer(v)) }
+
+    fn serialize_u8(self,  v: u8)  -> InfluxResult<()> { self.leaf(Value::Unsigned(v.into())) }
+    fn serialize_u16(self, v: u16) -> InfluxResult<()> { self.leaf(Value::Unsigned(v.into())) }
+    fn serialize_u32(self, v: u32) -> InfluxResult<()> { self.leaf(Value::Unsigned(v.into())) }
+    fn serialize_u64(self, v: u64) -> InfluxResult<()> { self.leaf(Value::Unsigned(v)) }
+
+    fn serialize_i128(self, v: i128) -> InfluxResult<()>
+    {
+        match i64::try_from(v)
+        {
+            Ok(v)  => { self.leaf(Value::Integer(v)) }
+            Err(_) => { Err(self.unsupported("an i128 beyond 64 bits")) }
+        }
+    }
+
+    fn serialize_u128(self, v: u128) -> InfluxResult<()>
+    {
+        match u64::try_from(v)
+        {
+            Ok(v)  => { self.leaf(Value::Unsigned(v)) }
+            Err(_) => { Err(self.unsupported("an u128 beyond 64 bits")) }
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> InfluxResult<()> { self.leaf(Value::from(v)) }
+    fn serialize_f64(self, v: f64) -> InfluxResult<()> { self.leaf(Value::Float(v)) }
+
+    fn serialize_char(self, v: char) -> InfluxResult<()> { self.leaf(Value::String(v.to_string())) }
+    fn serialize_str(self,  v: &str) -> InfluxResult<()> { self.leaf(Value::String(v.to_owned())) }
+
+    fn serialize_bytes(self, _: &[u8]) -> InfluxResult<()>
+    {
+        Err(self.unsupported("bytes"))
+    }
+
+    // absent values are skipped
+    fn serialize_none(self) -> InfluxResult<()> { Ok(()) }
+    fn serialize_unit(self) -> InfluxResult<()> { Ok(()) }
+
+    fn serialize_unit_struct(self, _: &'static str) -> InfluxResult<()> { Ok(()) }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> InfluxResult<()>
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, variant: &'static str) -> InfluxResult<()>
+    {
+        self.leaf(Value::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _: &'static str, value: &T) -> InfluxResult<()>
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(mut self, _: &'static str, _: u32, variant: &'static str, value: &T) -> InfluxResult<()>
+        where T: Serialize + ?Sized
+    {
+        value.serialize(self.nested(variant))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> InfluxResult<Self::SerializeSeq>
+    {
+        Err(self.unsupported("a sequence"))
+    }
+
+    fn serialize_tuple(self, _: usize) -> InfluxResult<Self::SerializeTuple>
+    {
+        Err(self.unsupported("a tuple"))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> InfluxResult<Self::SerializeTupleStruct>
+    {
+        Err(self.unsupported("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(self, _: &'static str, _: u32, _: &'static str, _: usize) -> InfluxResult<Self::SerializeTupleVariant>
+    {
+        Err(self.unsupported("a tuple variant"))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> InfluxResult<Self::SerializeMap>
+    {
+        Ok(FlatMap {inner: self, key: None})
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> InfluxResult<Self::SerializeStruct>
+    {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(mut self, _: &'static str, _: u32, variant: &'static str, _: usize) -> InfluxResult<Self::SerializeStructVariant>
+    {
+        self.prefix = self.nested(variant).prefix;
+        Ok(self)
+    }
+}
+
+
+impl<'a> ser::SerializeStruct for Flattener<'a>
+{
+    type Ok    = ();
+    type Error = InfluxError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> InfluxResult<()>
+    {
+        value.serialize(self.nested(key))
+    }
+
+    fn end(self) -> InfluxResult<()>
+    {
+        Ok(())
+    }
+}
+
+
+impl<'a> ser::SerializeStructVariant for Flattener<'a>
+{
+    type Ok    = ();
+    type Error = InfluxError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> InfluxResult<()>
+    {
+        value.serialize(self.nested(key))
+    }
+
+    fn end(self) -> InfluxResult<()>
+    {
+        Ok(())
+    }
+}
+
+
+/// Map flattening state, remembering the key until its value arrives
+struct FlatMap<'a>
+{
+    inner: Flattener<'a>,
+    key:   Option<String>,
+}
+
+
+impl<'a> ser::SerializeMap for FlatMap<'a>
+{
+    type Ok    = ();
+    type Error = InfluxError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> InfluxResult<()>
+    {
+        // keys are flattened on their own, which has to yield exactly one primitive to be usable
+        let mut leaves = Vec::new();
+
+        key.serialize(Flattener {leaves: &mut leaves, prefix: "key".to_owned(), separator: self.inner.separator})?;
+
+        match leaves.pop()
+        {
+            Some((_, value)) if leaves.is_empty() => { self.key = Some(value.to_string()); Ok(()) }
+            _                                     => { Err(self.inner.unsupported("a map key that is no primitive")) }
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> InfluxResult<()>
+    {
+        let key = self.key.take()
+            .ok_or_else(|| InfluxError::from("Map value serialized without key"))?;
+
+        value.serialize(self.inner.nested(&key))
+    }
+
+    fn end(self) -> InfluxResult<()>
+    {
+        Ok(())
+    }
+}
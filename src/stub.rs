@@ -0,0 +1,119 @@
+//!
+//! Stand-ins for the server and backlog in tests
+//!
+use crate::Record;
+use crate::Backlog;
+use crate::InfluxResult;
+
+use std::io::Read;
+use std::io::Write;
+
+use std::net::TcpListener;
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+
+
+/// Backlog kept in memory, shared with the test to look into
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MemoryBacklog(Arc<Mutex<Vec<String>>>);
+
+
+impl MemoryBacklog
+{
+    pub(crate) fn lines(&self) -> Vec<String>
+    {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+
+impl Backlog for MemoryBacklog
+{
+    fn read_pending(&mut self) -> InfluxResult<Vec<Record>>
+    {
+        self.lines().iter()
+            .map(|lines| Record::from_lines("org", "bucket", Default::default(), lines))
+            .collect()
+    }
+
+    fn write_pending(&mut self, record: &Record) -> InfluxResult<()>
+    {
+        self.0.lock().unwrap().push(lines(record)); Ok(())
+    }
+
+    fn truncate_pending(&mut self, record: &Record) -> InfluxResult<()>
+    {
+        let lines = lines(record);
+
+        self.0.lock().unwrap().retain(|pending| *pending != lines); Ok(())
+    }
+}
+
+
+fn lines(record: &Record) -> String
+{
+    let mut buffer = Vec::new();
+
+    record.write_lines(&mut buffer).unwrap();
+
+    String::from_utf8(buffer).unwrap()
+}
+
+
+/// Answer a request with each of the given statuses in turn, reporting the body of each one answered
+pub(crate) fn serve(statuses: Vec<u16>) -> (String, mpsc::Receiver<String>)
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url      = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for status in statuses
+        {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut request = Vec::new();
+            let mut buffer  = [0u8; 4096];
+
+            while let Ok(read) = stream.read(&mut buffer)
+            {
+                request.extend_from_slice(&buffer[..read]);
+
+                if read == 0 || body(&request).is_some() {
+                    break;
+                }
+            }
+
+            let reply = format!("HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+
+            let _ = stream.write_all(reply.as_bytes());
+            let _ = sender.send(body(&request).unwrap_or_default());
+        }
+    });
+
+    (url, receiver)
+}
+
+
+/// Body of the request once it arrived completely
+fn body(request: &[u8]) -> Option<String>
+{
+    let text = String::from_utf8_lossy(request);
+    let end  = text.find("\r\n\r\n")?;
+
+    let length = text[..end].lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    if request.len() >= end + 4 + length {
+        Some(String::from_utf8_lossy(&request[end + 4..end + 4 + length]).into_owned())
+    } else {
+        None
+    }
+}
@@ -0,0 +1,62 @@
+//!
+//! Organization or Bucket a Record gets Written to
+//!
+use std::fmt;
+
+
+/// Organization or bucket, addressed either by name or by ID. Names are convenient but may get renamed or be reused
+/// across environments, while IDs stay put.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Target
+{
+    /// Addressed by its name
+    Name(String),
+
+    /// Addressed by the ID InfluxDB assigned to it
+    Id(String),
+}
+
+
+impl Target
+{
+    /// Target addressed by name
+    pub fn name(name: &str) -> Self
+    {
+        Target::Name(name.to_owned())
+    }
+
+    /// Target addressed by ID
+    pub fn id(id: &str) -> Self
+    {
+        Target::Id(id.to_owned())
+    }
+
+    /// Name or ID, whichever it is addressed by
+    pub fn as_str(&self) -> &str
+    {
+        match self
+        {
+            Target::Name(name) => { name }
+            Target::Id(id)     => { id }
+        }
+    }
+
+    /// Whether it is addressed by ID
+    pub fn is_id(&self) -> bool
+    {
+        matches!(self, Target::Id(_))
+    }
+}
+
+
+impl fmt::Display for Target
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            Target::Name(name) => { write!(f, "{}", name) }
+            Target::Id(id)     => { write!(f, "id:{}", id) }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//!
+//! Conversion of Foreign Time Types into Timestamps
+//!
+use crate::DateTime;
+use crate::Precision;
+
+use chrono::TimeZone;
+
+use std::convert::TryFrom;
+use std::time::SystemTime;
+
+
+/// Conversion into the timestamp of a [Measurement](struct.Measurement.html), as taken by
+/// [Measurement::timestamp](struct.Measurement.html#method.timestamp).
+///
+/// Implemented for chrono datetimes of any timezone, `NaiveDateTime` taken as UTC, `std::time::SystemTime`, epoch
+/// integers paired with their [Precision](enum.Precision.html) and, with the `time` feature, `time::OffsetDateTime`.
+/// All of them keep nanosecond precision. Instants beyond what InfluxDB is able to store are kept as they are, so they
+/// get rejected once the record is written instead of being silently moved. Only those beyond the range of chrono
+/// itself saturate.
+///
+/// ## Examples
+///
+/// ```rust
+/// use influxc::Record;
+/// use influxc::Precision;
+///
+/// let mut rec = Record::new("org", "bucket")
+///     .precision(Precision::Nanoseconds);
+///
+/// let naive = chrono::NaiveDate::from_ymd_opt(2019, 5, 2).unwrap()
+///     .and_hms_nano_opt(16, 12, 41, 123_456_789).unwrap();
+///
+/// let offset = chrono::DateTime::parse_from_rfc3339("2019-05-02T13:12:41.123456789-03:00").unwrap();
+///
+/// let system = std::time::UNIX_EPOCH + std::time::Duration::new(1556813561, 123_456_789);
+///
+/// rec.measurement("m").field("v", 1).timestamp(naive);
+/// rec.measurement("m").field("v", 1).timestamp(offset);
+/// rec.measurement("m").field("v", 1).timestamp(system);
+/// rec.measurement("m").field("v", 1).timestamp((1556813561123456789, Precision::Nanoseconds));
+/// rec.measurement("m").field("v", 1).timestamp((1556813561123456, Precision::Microseconds));
+///
+/// let mut buffer = Vec::new();
+/// rec.write_lines(&mut buffer).unwrap();
+///
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "\
+///     m v=1i 1556813561123456789\n\
+///     m v=1i 1556813561123456789\n\
+///     m v=1i 1556813561123456789\n\
+///     m v=1i 1556813561123456789\n\
+///     m v=1i 1556813561123456000\n\
+/// ");
+/// ```
+pub trait IntoTimestamp
+{
+    /// Convert into an UTC datetime
+    fn into_timestamp(self) -> DateTime;
+}
+
+
+impl<Tz: TimeZone> IntoTimestamp for chrono::DateTime<Tz>
+{
+    fn into_timestamp(self) -> DateTime
+    {
+        self.with_timezone(&chrono::Utc)
+    }
+}
+
+
+impl IntoTimestamp for chrono::NaiveDateTime
+{
+    fn into_timestamp(self) -> DateTime
+    {
+        chrono::Utc.from_utc_datetime(&self)
+    }
+}
+
+
+impl IntoTimestamp for SystemTime
+{
+    fn into_timestamp(self) -> DateTime
+    {
+        let nanos = match self.duration_since(SystemTime::UNIX_EPOCH)
+        {
+            Ok(since)  => {   since.as_nanos() as i128 }
+            Err(until) => { -(until.duration().as_nanos() as i128) }
+        };
+
+        from_nanos(nanos)
+    }
+}
+
+
+/// Epoch integer in the unit of the given precision
+impl IntoTimestamp for (i64, Precision)
+{
+    fn into_timestamp(self) -> DateTime
+    {
+        let (timestamp, precision) = self;
+
+        from_nanos(i128::from(timestamp) * precision.nanos_per_unit())
+    }
+}
+
+
+#[cfg(feature="time")]
+impl IntoTimestamp for time::OffsetDateTime
+{
+    fn into_timestamp(self) -> DateTime
+    {
+        from_nanos(self.unix_timestamp_nanos())
+    }
+}
+
+
+/// Datetime from nanoseconds since the epoch, split into seconds and subsecond nanoseconds to reach beyond the
+/// roughly 292 years around 1970 a single i64 of nanoseconds covers
+fn from_nanos(nanos: i128) -> DateTime
+{
+    let secs = nanos.div_euclid(1_000_000_000);
+    let subs = nanos.rem_euclid(1_000_000_000) as u32;
+
+    i64::try_from(secs).ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, subs))
+        .unwrap_or_else(|| saturate(nanos < 0))
+}
+
+
+fn saturate(negative: bool) -> DateTime
+{
+    if negative { DateTime::MIN_UTC } else { DateTime::MAX_UTC }
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Rounding;
+
+    use std::time::Duration;
+
+
+    /// Error message of converting the instant to a timestamp
+    fn rejection<T: IntoTimestamp>(instant: T) -> String
+    {
+        Precision::Nanoseconds.to_timestamp(&instant.into_timestamp(), Rounding::Truncate)
+            .unwrap_err()
+            .to_string()
+    }
+
+
+    #[test]
+    fn keeps_instants_beyond_nanosecond_range()
+    {
+        let year_2300: i64 = 10_413_792_000;
+        let offset          = Duration::from_secs(year_2300 as u64);
+
+        assert!(rejection((year_2300, Precision::Seconds)).contains("2300-01-01 00:00:00 UTC"));
+        assert!(rejection((year_2300 * 1000 + 5, Precision::Milliseconds)).contains("2300-01-01 00:00:00.005 UTC"));
+        assert!(rejection(SystemTime::UNIX_EPOCH + offset + Duration::from_nanos(7)).contains("00:00:00.000000007"));
+
+        assert!(rejection((-year_2300, Precision::Seconds)).contains("1640-01-01 00:00:00 UTC"));
+        assert!(rejection(SystemTime::UNIX_EPOCH - offset).contains("1640-01-01 00:00:00 UTC"));
+    }
+
+
+    #[test]
+    fn converts_negative_instants()
+    {
+        assert_eq!((-1_500, Precision::Milliseconds).into_timestamp().to_rfc3339(), "1969-12-31T23:59:58.500+00:00");
+        let before = SystemTime::UNIX_EPOCH - Duration::from_nanos(1);
+
+        assert_eq!(before.into_timestamp().to_rfc3339(), "1969-12-31T23:59:59.999999999+00:00");
+    }
+
+
+    #[test]
+    fn saturates_beyond_chrono_range()
+    {
+        assert_eq!((i64::MAX, Precision::Seconds).into_timestamp(), DateTime::MAX_UTC);
+        assert_eq!((i64::MIN, Precision::Seconds).into_timestamp(), DateTime::MIN_UTC);
+    }
+}
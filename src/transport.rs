@@ -0,0 +1,344 @@
+//!
+//! TLS and HTTP Settings of the Connection to InfluxDB
+//!
+use crate::InfluxResult;
+use crate::InfluxErrorAnnotate;
+
+use crate::ReqwProxy;
+use crate::ReqwClient;
+use crate::ReqwIdentity;
+use crate::ReqwCertificate;
+
+#[cfg(feature="async")] use crate::ReqwAsyncClient;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+
+/// How to reach the server, as set on the [ClientBuilder](struct.ClientBuilder.html). Files are only read once the
+/// client gets built, so missing or malformed ones fail [finish](struct.ClientBuilder.html#method.finish).
+#[derive(Debug, Default)]
+pub(crate) struct Transport
+{
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) timeout:         Option<Duration>,
+
+    pub(crate) ca_certs: Vec<PathBuf>,
+    pub(crate) identity: Option<(PathBuf, PathBuf)>,
+
+    pub(crate) proxy:    Option<String>,
+    pub(crate) no_proxy: bool,
+
+    /// Left to the deprecated `INFLUX_UNSAFE_TLS` environment variable unless set explicitly
+    pub(crate) accept_invalid_certs: Option<bool>,
+}
+
+
+/// Apply the settings to either of the reqwest client builders, which share their methods but not a trait
+macro_rules! configure {
+    ($transport:expr, $builder:expr) => {{
+        let transport = $transport;
+        let mut builder = $builder.danger_accept_invalid_certs(transport.accept_invalid_certs()?);
+
+        if let Some(timeout) = transport.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        if let Some(timeout) = transport.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        for cert in transport.certificates()? {
+            builder = builder.add_root_certificate(cert);
+        }
+
+        if let Some(identity) = transport.identity()? {
+            builder = builder.identity(identity);
+        }
+
+        if let Some(proxy) = transport.proxy()? {
+            builder = builder.proxy(proxy);
+        } else if transport.no_proxy {
+            builder = builder.no_proxy();
+        }
+
+        builder.build()?
+    }};
+}
+
+
+impl Transport
+{
+    /// HTTP client for the blocking [Client](struct.Client.html)
+    pub(crate) fn blocking(&self) -> InfluxResult<ReqwClient>
+    {
+        Ok(configure!(self, ReqwClient::builder()))
+    }
+
+    /// HTTP client for the [AsyncClient](struct.AsyncClient.html)
+    #[cfg(feature="async")]
+    pub(crate) fn asynchronous(&self) -> InfluxResult<ReqwAsyncClient>
+    {
+        Ok(configure!(self, ReqwAsyncClient::builder()))
+    }
+
+    /// Whether to accept invalid certificates, as set explicitly or else by the deprecated `INFLUX_UNSAFE_TLS`
+    /// environment variable
+    fn accept_invalid_certs(&self) -> InfluxResult<bool>
+    {
+        if let Some(accept) = self.accept_invalid_certs {
+            return Ok(accept);
+        }
+
+        match std::env::var("INFLUX_UNSAFE_TLS")
+        {
+            Ok(value) => {
+                let accept = value.parse()
+                    .annotate(format!("While parsing INFLUX_UNSAFE_TLS: {}", value))?;
+
+                if accept {
+                    warn!("INFLUX_UNSAFE_TLS is deprecated, use ClientBuilder::danger_accept_invalid_certs instead");
+                }
+
+                Ok(accept)
+            }
+
+            Err(_) => { Ok(false) }
+        }
+    }
+
+    fn certificates(&self) -> InfluxResult<Vec<ReqwCertificate>>
+    {
+        let mut certs = Vec::new();
+
+        for path in self.ca_certs.iter()
+        {
+            let pem = std::fs::read(path)
+                .annotate(format!("While reading CA certificates: {:#?}", path))?;
+
+            certs.extend(ReqwCertificate::from_pem_bundle(&pem)
+                .annotate(format!("While parsing CA certificates: {:#?}", path))?);
+        }
+
+        Ok(certs)
+    }
+
+    fn identity(&self) -> InfluxResult<Option<ReqwIdentity>>
+    {
+        match &self.identity
+        {
+            None => { Ok(None) }
+
+            Some((cert, key)) => {
+                let cert_pem = std::fs::read(cert)
+                    .annotate(format!("While reading client certificate: {:#?}", cert))?;
+
+                let key_pem = std::fs::read(key)
+                    .annotate(format!("While reading client key: {:#?}", key))?;
+
+                let identity = ReqwIdentity::from_pkcs8_pem(&cert_pem, &key_pem)
+                    .annotate(format!("While parsing client identity: {:#?}", cert))?;
+
+                Ok(Some(identity))
+            }
+        }
+    }
+
+    fn proxy(&self) -> InfluxResult<Option<ReqwProxy>>
+    {
+        match &self.proxy
+        {
+            None      => { Ok(None) }
+            Some(url) => {
+                let proxy = ReqwProxy::all(url.as_str())
+                    .annotate(format!("While parsing proxy URL: {}", url))?;
+
+                Ok(Some(proxy))
+            }
+        }
+    }
+}
+
+
+/// Against a local TLS listener, with the certificates of `tests/tls` issued by a CA of their own
+#[cfg(test)]
+mod tests
+{
+    use crate::Client;
+    use crate::Record;
+    use crate::Credentials;
+    use crate::ClientBuilder;
+    use crate::InfluxResult;
+
+    use openssl::ssl::SslMethod;
+    use openssl::ssl::SslAcceptor;
+    use openssl::ssl::SslFiletype;
+    use openssl::ssl::SslVerifyMode;
+    use openssl::nid::Nid;
+
+    use std::io::Read;
+    use std::io::Write;
+
+    use std::net::TcpListener;
+
+    use std::sync::mpsc;
+    use std::thread;
+
+
+    fn fixture(name: &str) -> String
+    {
+        format!("{}/tests/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+
+    /// Accept TLS connections answering every request with 204, reporting the common name of the client certificate
+    /// of each one answered
+    fn serve(require_client_cert: bool) -> (String, mpsc::Receiver<Option<String>>)
+    {
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+
+        acceptor.set_private_key_file(fixture("server.key"), SslFiletype::PEM).unwrap();
+        acceptor.set_certificate_chain_file(fixture("server.pem")).unwrap();
+
+        if require_client_cert {
+            acceptor.set_ca_file(fixture("ca.pem")).unwrap();
+            acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        }
+
+        let acceptor = acceptor.build();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url      = format!("https://127.0.0.1:{}", listener.local_addr().unwrap().port());
+
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming()
+            {
+                let mut stream = match acceptor.accept(stream.unwrap()) {
+                    Ok(stream) => { stream }
+                    Err(_)     => { continue; }
+                };
+
+                let mut request = Vec::new();
+                let mut buffer  = [0u8; 4096];
+
+                // the request is small enough to arrive along with its headers, read until the body is complete
+                while let Ok(read) = stream.read(&mut buffer)
+                {
+                    request.extend_from_slice(&buffer[..read]);
+
+                    if read == 0 || is_complete(&request) {
+                        break;
+                    }
+                }
+
+                let client = stream.ssl().peer_certificate()
+                    .and_then(|cert| {
+                        cert.subject_name().entries_by_nid(Nid::COMMONNAME).next()
+                            .and_then(|name| String::from_utf8(name.data().as_slice().to_vec()).ok())
+                    });
+
+                let _ = stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+                let _ = stream.shutdown();
+
+                if sender.send(client).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (url, receiver)
+    }
+
+
+    fn is_complete(request: &[u8]) -> bool
+    {
+        let text = String::from_utf8_lossy(request);
+
+        match text.find("\r\n\r\n")
+        {
+            None      => { false }
+            Some(end) => {
+                let length = text[..end].lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+
+                request.len() >= end + 4 + length
+            }
+        }
+    }
+
+
+    fn write(builder: ClientBuilder) -> InfluxResult<()>
+    {
+        let mut rec = Record::new("org", "bucket");
+
+        rec.measurement("tls").field("ok", true);
+
+        builder.finish()?.write(&rec)
+    }
+
+
+    fn build(url: &str) -> ClientBuilder
+    {
+        Client::build(url.to_owned(), Credentials::from_token("TOKEN"))
+            .danger_accept_invalid_certs(false)
+    }
+
+
+    #[test]
+    fn trusts_ca_certificate()
+    {
+        let (url, served) = serve(false);
+
+        write(build(&url).ca_certificate(fixture("ca.pem"))).unwrap();
+
+        assert_eq!(served.recv().unwrap(), None);
+    }
+
+
+    #[test]
+    fn rejects_untrusted_certificate()
+    {
+        let (url, served) = serve(false);
+
+        assert!(write(build(&url)).is_err());
+        assert!(served.try_recv().is_err());
+    }
+
+
+    #[test]
+    fn accepts_invalid_certificate_when_asked_to()
+    {
+        let (url, served) = serve(false);
+
+        write(build(&url).danger_accept_invalid_certs(true)).unwrap();
+
+        assert_eq!(served.recv().unwrap(), None);
+    }
+
+
+    #[test]
+    fn authenticates_with_identity()
+    {
+        let (url, served) = serve(true);
+
+        let trusting = || build(&url).ca_certificate(fixture("ca.pem"));
+
+        assert!(write(trusting()).is_err());
+
+        write(trusting().identity(fixture("client.pem"), fixture("client.key"))).unwrap();
+
+        assert_eq!(served.recv().unwrap().as_deref(), Some("influxc test client"));
+    }
+
+
+    #[test]
+    fn fails_on_missing_files()
+    {
+        assert!(build("https://127.0.0.1:8086").ca_certificate(fixture("missing.pem")).finish().is_err());
+        assert!(build("https://127.0.0.1:8086").identity(fixture("client.pem"), fixture("missing.key")).finish().is_err());
+    }
+}
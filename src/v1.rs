@@ -0,0 +1,328 @@
+//!
+//! Writing to InfluxDB 1.x
+//!
+use crate::b64;
+use crate::json;
+
+use crate::Value;
+use crate::Record;
+use crate::Target;
+use crate::Credentials;
+
+use crate::writing;
+
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use crate::ApiGenericError;
+use crate::ApiOversizeError;
+use crate::ApiMalformationError;
+
+use crate::ReqwHeaderMap;
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+
+/// How user and password of [basic credentials](enum.Credentials.html#variant.Basic) get sent to InfluxDB 1.x, which
+/// has no sign in but takes them along with every request. Tokens always go in the `Authorization` header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum V1Auth
+{
+    /// In the `Authorization` header as HTTP basic auth
+    Basic,
+
+    /// As `u` and `p` query parameters
+    Query,
+}
+
+
+/// Reply of InfluxDB 1.x on failure
+#[derive(Debug, Deserialize)]
+struct ApiV1Error
+{
+    error: String,
+}
+
+
+/// Database and retention policy the record goes to, named by its organization and bucket
+pub(crate) fn database(record: &Record) -> InfluxResult<(&str, &str)>
+{
+    match (&record.org, &record.bucket)
+    {
+        (Target::Name(db), Target::Name(rp)) => { Ok((db, rp)) }
+
+        _ => { Err("InfluxDB 1.x addresses databases and retention policies by name only".into()) }
+    }
+}
+
+
+/// InfluxDB 1.x knows no unsigned integers, so unsigned fields get sent as integers. Those beyond the range of an
+/// integer are rejected, as the server would never accept them.
+pub(crate) fn unsigned_as_integers(record: &mut Cow<Record>) -> InfluxResult<()>
+{
+    let unsigned = record.measurements.iter()
+        .any(|msrmt| msrmt.fields.values().any(|value| matches!(value, Value::Unsigned(_))));
+
+    if ! unsigned {
+        return Ok(());
+    }
+
+    for msrmt in record.to_mut().measurements.iter_mut()
+    {
+        let name = &msrmt.name;
+
+        for (key, value) in msrmt.fields.iter_mut()
+        {
+            if let Value::Unsigned(number) = value
+            {
+                let number = i64::try_from(*number).map_err(|_| InfluxError::from(format!(
+                    "Field {} of measurement {} holds {}, which exceeds the integers of InfluxDB 1.x", key, name, number
+                )))?;
+
+                *value = Value::Integer(number);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Query parameters of the write request. An empty retention policy is left to the default one of the database.
+pub(crate) fn write_query(record: &Record, creds: &Credentials, auth: V1Auth)
+    -> InfluxResult<Vec<(&'static str, String)>>
+{
+    let (db, rp) = database(record)?;
+
+    let mut query = vec![("db", db.to_owned())];
+
+    if ! rp.is_empty() {
+        query.push(("rp", rp.to_owned()));
+    }
+
+    query.push(("precision", record.precision.to_string()));
+
+    if let (V1Auth::Query, Credentials::Basic{user, passwd, ..}) = (auth, creds)
+    {
+        query.push(("u", user.clone()));
+        query.push(("p", passwd.clone()));
+    }
+
+    Ok(query)
+}
+
+
+/// Header authorizing the write request, unless the credentials go in the query
+pub(crate) fn header(creds: &Credentials, auth: V1Auth) -> Option<(&'static str, String)>
+{
+    match (creds, auth)
+    {
+        (Credentials::Basic{user, passwd, ..}, V1Auth::Basic) => {
+            Some(("Authorization", format!("Basic {}", b64::encode(format!("{}:{}", user, passwd)))))
+        }
+
+        (Credentials::Basic{..}, V1Auth::Query) => { None }
+
+        (Credentials::Token{token}, _) => { Some(("Authorization", format!("Token {}", token))) }
+    }
+}
+
+
+/// Map the reply of the 1.x write endpoint onto the same errors as the one of 2.x
+pub(crate) fn write_reply(status: u16, headers: &ReqwHeaderMap, body: &[u8]) -> InfluxResult<()>
+{
+    match status
+    {
+        200 | 204 => { return Ok(()); }
+
+        429 => { return Err(InfluxError::WriteOverquota(writing::delay_error(headers, b""))); }
+        503 => { return Err(InfluxError::WriteUnready(writing::delay_error(headers, b""))); }
+
+        _ => {}
+    }
+
+    let message = error_message(headers, body);
+
+    match status
+    {
+        400 => { Err(InfluxError::WriteMalformed(ApiMalformationError::new("invalid", message))) }
+        401 => { Err(InfluxError::WriteUnauthorized(ApiGenericError::new("unauthorized", message))) }
+        403 => { Err(InfluxError::WriteUnauthenticated(ApiGenericError::new("forbidden", message))) }
+        404 => { Err(InfluxError::WriteUnknown(ApiGenericError::new("not found", message))) }
+        413 => { Err(InfluxError::WriteOversized(ApiOversizeError::new(message))) }
+
+        _   => { Err(InfluxError::WriteUnknown(ApiGenericError::new("internal error", message))) }
+    }
+}
+
+
+/// Message of the `{"error": ...}` reply, falling back to the `X-Influxdb-Error` header and the plain body
+fn error_message(headers: &ReqwHeaderMap, body: &[u8]) -> String
+{
+    if let Ok(reply) = json::from_slice::<ApiV1Error>(body) {
+        return reply.error;
+    }
+
+    if let Some(error) = headers.get("X-Influxdb-Error").and_then(|value| value.to_str().ok()) {
+        return error.to_owned();
+    }
+
+    String::from_utf8_lossy(body).trim().to_owned()
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::Precision;
+    use crate::Compression;
+    use crate::writing::WriteOptions;
+
+
+    fn reply(status: u16, headers: &[(&'static str, &str)], body: &str) -> InfluxResult<()>
+    {
+        let mut map = ReqwHeaderMap::new();
+
+        for (name, value) in headers {
+            map.insert(*name, value.parse().unwrap());
+        }
+
+        write_reply(status, &map, body.as_bytes())
+    }
+
+
+    fn query(pairs: &[(&'static str, &str)]) -> Vec<(&'static str, String)>
+    {
+        pairs.iter().map(|(key, value)| (*key, value.to_string())).collect()
+    }
+
+
+    #[test]
+    fn maps_statuses()
+    {
+        let body = r#"{"error":"failed"}"#;
+
+        assert!(reply(200, &[], "").is_ok());
+        assert!(reply(204, &[], "").is_ok());
+
+        assert!(matches!(reply(400, &[], body), Err(InfluxError::WriteMalformed(_))));
+        assert!(matches!(reply(401, &[], body), Err(InfluxError::WriteUnauthorized(_))));
+        assert!(matches!(reply(403, &[], body), Err(InfluxError::WriteUnauthenticated(_))));
+        assert!(matches!(reply(404, &[], body), Err(InfluxError::WriteUnknown(_))));
+        assert!(matches!(reply(413, &[], body), Err(InfluxError::WriteOversized(e)) if e.maxlen() == 0));
+        assert!(matches!(reply(500, &[], body), Err(InfluxError::WriteUnknown(_))));
+        assert!(matches!(reply(502, &[], body), Err(InfluxError::WriteUnknown(_))));
+    }
+
+
+    #[test]
+    fn maps_delays()
+    {
+        match reply(429, &[("Retry-After", "7")], r#"{"error":"too many requests"}"#)
+        {
+            Err(InfluxError::WriteOverquota(e)) => { assert_eq!(e.delay().as_secs(), 7); }
+            other                               => { panic!("Unexpected reply: {:?}", other); }
+        }
+
+        match reply(503, &[], "Service Unavailable")
+        {
+            Err(InfluxError::WriteUnready(e)) => { assert_eq!(e.delay().as_secs(), 0); }
+            other                             => { panic!("Unexpected reply: {:?}", other); }
+        }
+    }
+
+
+    #[test]
+    fn takes_message_from_json_header_or_text()
+    {
+        let message  = |headers, body| reply(401, headers, body).unwrap_err().to_string();
+        let expected = "WriteUnauthorized(code=unauthorized, message=authorization failed)";
+
+        assert_eq!(message(&[], r#"{"error":"authorization failed"}"#), expected);
+        assert_eq!(message(&[("X-Influxdb-Error", "authorization failed")], "<html>Unauthorized</html>"), expected);
+        assert_eq!(message(&[], "authorization failed\n"), expected);
+    }
+
+
+    #[test]
+    fn builds_query()
+    {
+        let basic = Credentials::from_basic("user", "secret");
+        let token = Credentials::from_token("TOKEN");
+
+        let rec = Record::new("telegraf", "autogen");
+
+        assert_eq!(write_query(&rec, &basic, V1Auth::Query).unwrap(),
+            query(&[("db", "telegraf"), ("rp", "autogen"), ("precision", "ns"), ("u", "user"), ("p", "secret")]));
+
+        assert_eq!(write_query(&rec, &basic, V1Auth::Basic).unwrap(),
+            query(&[("db", "telegraf"), ("rp", "autogen"), ("precision", "ns")]));
+
+        assert_eq!(write_query(&rec, &token, V1Auth::Query).unwrap(),
+            query(&[("db", "telegraf"), ("rp", "autogen"), ("precision", "ns")]));
+
+        let rec = Record::new("telegraf", "").precision(Precision::Seconds);
+
+        assert_eq!(write_query(&rec, &basic, V1Auth::Basic).unwrap(), query(&[("db", "telegraf"), ("precision", "s")]));
+
+        let rec = Record::targeting(Target::id("0261d8287f4d6000"), Target::name("autogen"));
+
+        assert!(write_query(&rec, &basic, V1Auth::Query).is_err());
+    }
+
+
+    #[test]
+    fn builds_header()
+    {
+        let basic = Credentials::from_basic("user", "secret");
+        let token = Credentials::from_token("TOKEN");
+
+        assert_eq!(header(&basic, V1Auth::Basic), Some(("Authorization", "Basic dXNlcjpzZWNyZXQ=".to_owned())));
+        assert_eq!(header(&basic, V1Auth::Query), None);
+        assert_eq!(header(&token, V1Auth::Query), Some(("Authorization", "Token TOKEN".to_owned())));
+    }
+
+
+    #[test]
+    fn sends_unsigned_as_integers()
+    {
+        let mut options = WriteOptions::default();
+
+        options.v1 = Some(V1Auth::Basic);
+
+        let mut rec = Record::new("telegraf", "").precision(Precision::Seconds);
+
+        rec.measurement("elevator")
+            .field("floor", 2u8)
+            .field("rides", i64::MAX as u64)
+            .timestamp((1556813561, Precision::Seconds));
+
+        let prepared = options.prepare(&rec).unwrap();
+        let (body, _) = prepared.to_write_body(&options.default_tags, Compression::Off).unwrap();
+
+        assert_eq!(String::from_utf8(body).unwrap(), "elevator floor=2i,rides=9223372036854775807i 1556813561\n");
+
+        rec.measurement("elevator").field("floor", u64::MAX);
+
+        let error = options.prepare(&rec).unwrap_err().to_string();
+
+        assert!(error.contains("18446744073709551615"), "{}", error);
+    }
+
+
+    #[test]
+    fn redacts_password_from_logs()
+    {
+        let mut options = WriteOptions::default();
+
+        options.v1 = Some(V1Auth::Query);
+
+        let request = options.write_request(&Record::new("telegraf", ""), &Credentials::from_basic("user", "secret"))
+            .unwrap();
+
+        assert_eq!(request.to_string(), "/write?db=telegraf&precision=ns&u=user&p=<redacted>");
+    }
+}
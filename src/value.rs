@@ -1,16 +1,23 @@
 //!
 //! InfluxDB Value Variants
 //!
+use crate::InfluxError;
+
 use std::fmt;
+use std::borrow::Cow;
+use std::convert::TryFrom;
 
 
 /// Type primitives as supported by InfluxDB and their conversions from/to Rust primitives
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Value
 {
     /// Self explanatory integer type
     #[serde(rename="i64")] Integer(i64),
 
+    /// Unsigned 64 bit integer type. Only supported by InfluxDB 2.x
+    #[serde(rename="u64")] Unsigned(u64),
+
     /// Self explanatory float type
     #[serde(rename="f64")] Float(f64),
 
@@ -23,22 +30,254 @@ pub enum Value
 }
 
 
+/// Type of a [Value](enum.Value.html), disregarding its content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType
+{
+    /// Type of [Value::Integer](enum.Value.html#variant.Integer)
+    Integer,
+
+    /// Type of [Value::Unsigned](enum.Value.html#variant.Unsigned)
+    Unsigned,
+
+    /// Type of [Value::Float](enum.Value.html#variant.Float)
+    Float,
+
+    /// Type of [Value::String](enum.Value.html#variant.String)
+    String,
+
+    /// Type of [Value::Boolean](enum.Value.html#variant.Boolean)
+    Boolean,
+}
+
+
+impl Value
+{
+    /// Type of this value, as InfluxDB tracks it per measurement field
+    pub fn value_type(&self) -> ValueType
+    {
+        match self
+        {
+            Value::Integer(_)  => ValueType::Integer,
+            Value::Unsigned(_) => ValueType::Unsigned,
+            Value::Float(_)    => ValueType::Float,
+            Value::String(_)   => ValueType::String,
+            Value::Boolean(_)  => ValueType::Boolean,
+        }
+    }
+
+    /// Whether both values get written the same. Unlike `==`, floats compare by their bits, so NaN equals NaN.
+    pub(crate) fn is_identical(&self, other: &Value) -> bool
+    {
+        match (self, other)
+        {
+            (Value::Float(a), Value::Float(b)) => { a.to_bits() == b.to_bits() }
+            (a, b)                             => { a == b }
+        }
+    }
+}
+
+
 impl fmt::Display for Value
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
     {
         match self
         {
-            Value::Integer(v) => v.fmt(f),
-            Value::Float(v)   => v.fmt(f),
-            Value::String(v)  => v.fmt(f),
-            Value::Boolean(v) => v.fmt(f),
+            Value::Integer(v)  => v.fmt(f),
+            Value::Unsigned(v) => v.fmt(f),
+            Value::Float(v)    => v.fmt(f),
+            Value::String(v)   => v.fmt(f),
+            Value::Boolean(v)  => v.fmt(f),
         }
     }
 }
 
 
-impl From<i64>    for Value { fn from(other: i64)    -> Self { Value::Integer(other) }}
-impl From<f64>    for Value { fn from(other: f64)    -> Self { Value::Float(other) }}
-impl From<String> for Value { fn from(other: String) -> Self { Value::String(other) }}
-impl From<bool>   for Value { fn from(other: bool)   -> Self { Value::Boolean(other) }}
+/// Conversion into the value of a field, as taken by [Measurement::field](struct.Measurement.html#method.field).
+/// Implemented for everything convertible into a [Value](enum.Value.html), and for `Option` of it, which leaves the
+/// field out when `None`.
+///
+/// ## Examples
+///
+/// ```rust
+/// use influxc::Record;
+/// use influxc::Value;
+///
+/// use std::convert::TryFrom;
+///
+/// let mut rec = Record::new("org", "bucket")
+///     .server_timestamps();
+///
+/// rec.measurement("reading")
+///     .field("floor", 2u8)
+///     .field("temp", 21.7f32)
+///     .field("unit", 'C')
+///     .field("device", "dev-7")
+///     .field("humidity", None::<f64>);
+///
+/// let mut buffer = Vec::new();
+/// rec.write_lines(&mut buffer).unwrap();
+///
+/// assert_eq!(buffer, b"reading device=\"dev-7\",floor=2u,temp=21.7,unit=\"C\"\n");
+///
+/// let floor = &rec.measurements()[0].fields()["floor"];
+///
+/// assert_eq!(i32::try_from(floor).unwrap(), 2);
+/// assert!(bool::try_from(floor).is_err());
+/// assert!(String::try_from(Value::from(21.7)).is_err());
+/// ```
+pub trait IntoFieldValue
+{
+    /// Convert into the field value, if any
+    fn into_field_value(self) -> Option<Value>;
+}
+
+
+impl<T: Into<Value>> IntoFieldValue for T
+{
+    fn into_field_value(self) -> Option<Value>
+    {
+        Some(self.into())
+    }
+}
+
+
+impl<T: Into<Value>> IntoFieldValue for Option<T>
+{
+    fn into_field_value(self) -> Option<Value>
+    {
+        self.map(Into::into)
+    }
+}
+
+
+impl From<i64>   for Value { fn from(other: i64)   -> Self { Value::Integer(other) }}
+impl From<i32>   for Value { fn from(other: i32)   -> Self { Value::Integer(other.into()) }}
+impl From<i16>   for Value { fn from(other: i16)   -> Self { Value::Integer(other.into()) }}
+impl From<i8>    for Value { fn from(other: i8)    -> Self { Value::Integer(other.into()) }}
+impl From<isize> for Value { fn from(other: isize) -> Self { Value::Integer(other as i64) }}
+impl From<u64>   for Value { fn from(other: u64)   -> Self { Value::Unsigned(other) }}
+impl From<u32>   for Value { fn from(other: u32)   -> Self { Value::Unsigned(other.into()) }}
+impl From<u16>   for Value { fn from(other: u16)   -> Self { Value::Unsigned(other.into()) }}
+impl From<u8>    for Value { fn from(other: u8)    -> Self { Value::Unsigned(other.into()) }}
+impl From<usize> for Value { fn from(other: usize) -> Self { Value::Unsigned(other as u64) }}
+impl From<f64>   for Value { fn from(other: f64)   -> Self { Value::Float(other) }}
+impl From<bool>  for Value { fn from(other: bool)  -> Self { Value::Boolean(other) }}
+impl From<char>  for Value { fn from(other: char)  -> Self { Value::String(other.to_string()) }}
+
+impl From<String>        for Value { fn from(other: String)        -> Self { Value::String(other) }}
+impl From<&str>          for Value { fn from(other: &str)          -> Self { Value::String(other.to_owned()) }}
+impl From<&String>       for Value { fn from(other: &String)       -> Self { Value::String(other.clone()) }}
+impl From<Cow<'_, str>>  for Value { fn from(other: Cow<'_, str>)  -> Self { Value::String(other.into_owned()) }}
+
+
+/// Widening the binary value would write `21.7f32` as `21.700000762939453`. Going through the shortest decimal
+/// representation keeps the value as written.
+impl From<f32> for Value
+{
+    fn from(other: f32) -> Self
+    {
+        Value::Float(other.to_string().parse().unwrap_or_else(|_| other.into()))
+    }
+}
+
+
+/// Native types a [Value](enum.Value.html) converts back into. Integers convert as long as they fit, regardless of
+/// being signed in InfluxDB or not.
+macro_rules! impl_try_from_value
+{
+    ($($native:ty => |$value:ident| $convert:expr),* $(,)?) => {
+        $(
+            impl TryFrom<&Value> for $native
+            {
+                type Error = InfluxError;
+
+                fn try_from($value: &Value) -> Result<Self, Self::Error>
+                {
+                    $convert.ok_or_else(|| mismatch($value, stringify!($native)))
+                }
+            }
+
+            impl TryFrom<Value> for $native
+            {
+                type Error = InfluxError;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error>
+                {
+                    Self::try_from(&value)
+                }
+            }
+        )*
+    };
+}
+
+
+impl_try_from_value! {
+    i64   => |value| value.integer(),
+    i32   => |value| value.integer(),
+    i16   => |value| value.integer(),
+    i8    => |value| value.integer(),
+    isize => |value| value.integer(),
+    u64   => |value| value.integer(),
+    u32   => |value| value.integer(),
+    u16   => |value| value.integer(),
+    u8    => |value| value.integer(),
+    usize => |value| value.integer(),
+
+    f64 => |value| if let Value::Float(v) = *value { Some(v) } else { None },
+    f32 => |value| if let Value::Float(v) = *value { Some(v as f32) } else { None },
+
+    bool => |value| if let Value::Boolean(v) = *value { Some(v) } else { None },
+}
+
+
+impl TryFrom<&Value> for String
+{
+    type Error = InfluxError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error>
+    {
+        match value
+        {
+            Value::String(v) => { Ok(v.clone()) }
+            _                => { Err(mismatch(value, "String")) }
+        }
+    }
+}
+
+
+impl TryFrom<Value> for String
+{
+    type Error = InfluxError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error>
+    {
+        match value
+        {
+            Value::String(v) => { Ok(v) }
+            _                => { Err(mismatch(&value, "String")) }
+        }
+    }
+}
+
+
+impl Value
+{
+    /// Either integer variant, as long as it fits the target type
+    fn integer<T: TryFrom<i64> + TryFrom<u64>>(&self) -> Option<T>
+    {
+        match *self
+        {
+            Value::Integer(v)  => { T::try_from(v).ok() }
+            Value::Unsigned(v) => { T::try_from(v).ok() }
+            _                  => { None }
+        }
+    }
+}
+
+
+fn mismatch(value: &Value, native: &str) -> InfluxError
+{
+    InfluxError::Error(format!("Unable to convert {:?} value {} into {}", value.value_type(), value, native))
+}
@@ -0,0 +1,532 @@
+//!
+//! Write Pipeline Shared by the Blocking and Async Clients
+//!
+use crate::v1;
+use crate::Record;
+use crate::V1Auth;
+use crate::Credentials;
+use crate::Compression;
+use crate::RetryPolicy;
+use crate::SchemaTracker;
+
+use crate::measurement::Tags;
+
+use crate::json;
+use crate::ChunkError;
+use crate::InfluxError;
+use crate::InfluxResult;
+
+use crate::ApiDelayError;
+use crate::ApiGenericError;
+use crate::ApiOversizeError;
+use crate::ApiMalformationError;
+
+use crate::ReqwHeaderMap;
+use crate::FlateGzipDecoder;
+
+use std::io::Read;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use std::sync::atomic::AtomicUsize;
+
+
+/// Checks and transformations every record goes through before being sent, and how it gets sent, as set on the
+/// [ClientBuilder](struct.ClientBuilder.html). Shared by all clones of a client, so what changes while writing is
+/// synchronized internally.
+#[derive(Debug, Default)]
+pub(crate) struct WriteOptions
+{
+    pub(crate) validate: bool,
+    pub(crate) compact:  bool,
+    pub(crate) schema:   Option<Mutex<SchemaTracker>>,
+
+    pub(crate) default_tags: Tags,
+
+    pub(crate) max_lines: Option<usize>,
+    pub(crate) max_bytes: Option<usize>,
+
+    /// Size limit learned from the server rejecting a request as too large, zero while unknown
+    learned_max_bytes: AtomicUsize,
+
+    pub(crate) retry:       Option<RetryPolicy>,
+    pub(crate) compression: Compression,
+
+    /// Write to the 1.x endpoint instead, sending basic credentials as given
+    pub(crate) v1: Option<V1Auth>,
+}
+
+
+/// Where and how to send a record
+pub(crate) struct WriteRequest
+{
+    pub(crate) path:  &'static str,
+    pub(crate) query: Vec<(&'static str, String)>,
+    pub(crate) auth:  Option<(&'static str, String)>,
+}
+
+
+/// Path and query for the logs, with the password of 1.x query authentication blanked out. Headers are left out
+/// altogether, as they carry the credentials otherwise.
+impl std::fmt::Display for WriteRequest
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        let query = self.query.iter()
+            .map(|(key, value)| format!("{}={}", key, if *key == "p" { "<redacted>" } else { value }))
+            .collect::<Vec<String>>()
+            .join("&");
+
+        write!(f, "{}?{}", self.path, query)
+    }
+}
+
+
+impl WriteOptions
+{
+    /// Validate, compact and type check the record as enabled, yielding it the way it is to be sent. Failing records
+    /// are rejected right away and never backlogged.
+    pub(crate) fn prepare<'r>(&self, record: &'r Record) -> InfluxResult<Cow<'r, Record>>
+    {
+        if self.v1.is_some() {
+            v1::database(record)?;
+        }
+
+        if self.validate {
+            record.validate()?;
+        }
+
+        let mut record = Cow::Borrowed(record);
+
+        if self.v1.is_some() {
+            v1::unsigned_as_integers(&mut record)?;
+        }
+
+        if self.compact
+        {
+            let mut copy = record.with_default_tags(&self.default_tags);
+            copy.compact()?;
+            record = Cow::Owned(copy);
+        }
+
+        if let Some(schema) = &self.schema
+        {
+            let mut schema = schema.lock()
+                .map_err(|_| InfluxError::from("Schema tracker poisoned by an earlier panic"))?;
+
+            let coercions = schema.inspect(&record)?;
+
+            if ! coercions.is_empty() {
+                SchemaTracker::coerce(record.to_mut(), &coercions);
+            }
+
+            schema.learn(&record);
+        }
+
+        Ok(record)
+    }
+
+    /// Record the way it goes into the backlog, with the client wide default tags merged in, so a replay writes the
+    /// very same tags
+    pub(crate) fn pending<'r>(&self, record: &'r Record) -> Cow<'r, Record>
+    {
+        if self.default_tags.is_empty() {
+            Cow::Borrowed(record)
+        } else {
+            Cow::Owned(record.with_default_tags(&self.default_tags))
+        }
+    }
+
+    /// Endpoint, query parameters and authorization of the request writing the record
+    pub(crate) fn write_request(&self, record: &Record, creds: &Credentials) -> InfluxResult<WriteRequest>
+    {
+        match self.v1
+        {
+            None => {
+                Ok(WriteRequest {
+                    path:  "/api/v2/write",
+                    query: record.to_write_query().to_vec(),
+                    auth:  Some(creds.header()?),
+                })
+            }
+
+            Some(auth) => {
+                Ok(WriteRequest {
+                    path:  "/write",
+                    query: v1::write_query(record, creds, auth)?,
+                    auth:  v1::header(creds, auth),
+                })
+            }
+        }
+    }
+
+    /// Map the reply of the write endpoint onto the outcome of the write
+    pub(crate) fn write_reply(&self, status: u16, headers: &ReqwHeaderMap, body: &[u8]) -> InfluxResult<()>
+    {
+        let body = decode_body(headers, body)?;
+
+        match self.v1
+        {
+            None    => { write_reply(status, headers, &body) }
+            Some(_) => { v1::write_reply(status, headers, &body) }
+        }
+    }
+
+    /// Split the record into chunks within the size limits
+    pub(crate) fn chunks(&self, record: &Record) -> InfluxResult<Chunks>
+    {
+        let mut pending = record.split(self.max_lines, self.max_bytes(), &self.default_tags)?;
+
+        if pending.len() > 1 {
+            info!("Splitting record into {} chunks", pending.len());
+        }
+
+        pending.reverse();
+
+        Ok(Chunks {pending, failed: Vec::new()})
+    }
+
+    /// Configured size limit, lowered to the one learned from the server
+    fn max_bytes(&self) -> Option<usize>
+    {
+        match (self.max_bytes, self.learned_max_bytes.load(Ordering::Relaxed))
+        {
+            (limit, 0)             => { limit }
+            (None, learned)        => { Some(learned) }
+            (Some(limit), learned) => { Some(limit.min(learned)) }
+        }
+    }
+
+    fn learn_max_bytes(&self, maxlen: usize)
+    {
+        let _ = self.learned_max_bytes.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+            Some(if current == 0 { maxlen } else { current.min(maxlen) })
+        });
+    }
+}
+
+
+/// Queue of chunks left to write, along with the ones that failed
+pub(crate) struct Chunks
+{
+    pending: Vec<(Range<usize>, Record)>,
+    failed:  Vec<(Record, ChunkError)>,
+}
+
+
+impl Chunks
+{
+    /// Next chunk to write, along with the indices of the measurements it covers
+    pub(crate) fn next(&mut self) -> Option<(Range<usize>, Record)>
+    {
+        self.pending.pop()
+    }
+
+    /// Take note of the outcome of writing a chunk. A chunk rejected as oversized gets split further along the length
+    /// the server reports, which is also remembered as limit for later writes.
+    pub(crate) fn report(&mut self, options: &WriteOptions, range: Range<usize>, chunk: Record,
+        result: InfluxResult<()>) -> InfluxResult<()>
+    {
+        match result
+        {
+            Ok(()) => {}
+
+            Err(InfluxError::WriteOversized(e)) if range.len() > 1 => {
+                let maxlen = usize::try_from(e.maxlen()).ok()
+                    .filter(|maxlen| *maxlen > 0)
+                    .unwrap_or(usize::MAX);
+
+                options.learn_max_bytes(maxlen);
+
+                // halving the lines as well guarantees progress, even if the server limit is off
+                let parts = chunk.split(Some(range.len().div_ceil(2)), Some(maxlen), &options.default_tags)?;
+
+                warn!("Chunk of {} measurements too large ({}), retrying in {} parts", range.len(), e, parts.len());
+
+                for (part, record) in parts.into_iter().rev() {
+                    self.pending.push((range.start + part.start .. range.start + part.end, record));
+                }
+            }
+
+            Err(e) => { self.failed.push((chunk, ChunkError::new(range, e))); }
+        }
+
+        Ok(())
+    }
+
+    /// Chunks that failed to be written
+    pub(crate) fn into_failed(self) -> Vec<(Record, ChunkError)>
+    {
+        self.failed
+    }
+}
+
+
+/// Outcome of writing the record in chunks. Records written in a single chunk fail with the plain error, just as if
+/// they had never been split.
+pub(crate) fn chunk_result(record: &Record, mut errors: Vec<ChunkError>) -> InfluxResult<()>
+{
+    match errors.len()
+    {
+        0 => { Ok(()) }
+        1 if errors[0].measurements() == (0..record.measurements.len()) => { Err(errors.remove(0).into_error()) }
+        _ => { Err(InfluxError::WritePartial(errors)) }
+    }
+}
+
+
+/// What is left of a backlogged record after replaying it
+pub(crate) struct Replay
+{
+    /// Whether the record in the backlog has to be replaced by the chunks to keep, as some got written or dropped
+    pub(crate) rewrite: bool,
+
+    /// Failed chunks worth another attempt
+    pub(crate) keep: Vec<Record>,
+
+    /// Outcome of the replay
+    pub(crate) result: InfluxResult<()>,
+}
+
+
+/// Sort the chunks of a backlogged record that failed to be written into the ones to keep backlogged and the ones to
+/// drop, as they hold single lines too large for the server to ever accept. Chunks that got written must not remain in
+/// the backlog, or the next replay writes them once more.
+pub(crate) fn replay(record: &Record, failed: Vec<(Record, ChunkError)>) -> Replay
+{
+    let unwritten = failed.iter()
+        .map(|(_, error)| error.measurements().len())
+        .sum::<usize>();
+
+    let mut rewrite = unwritten < record.measurements.len();
+    let mut keep    = Vec::new();
+    let mut errors  = Vec::new();
+
+    for (chunk, error) in failed
+    {
+        if let InfluxError::WriteOversized(e) = error.error() {
+            error!("Dropping backlogged measurements {:?} too large to ever be written: {}", error.measurements(), e);
+            rewrite = true;
+        } else {
+            keep.push(chunk);
+        }
+
+        errors.push(error);
+    }
+
+    let result = chunk_result(record, errors)
+        .map_err(|e| InfluxError::Annotated(format!("Unable to commit backlogged record: {}", e), Box::new(e)));
+
+    Replay {rewrite, keep, result}
+}
+
+
+/// Body of a reply, decompressed if the server gzipped it
+pub(crate) fn decode_body<'b>(headers: &ReqwHeaderMap, body: &'b [u8]) -> InfluxResult<Cow<'b, [u8]>>
+{
+    let gzipped = headers.get("Content-Encoding")
+        .map(|encoding| encoding.as_bytes().eq_ignore_ascii_case(b"gzip"))
+        .unwrap_or(false);
+
+    if ! gzipped {
+        return Ok(Cow::Borrowed(body));
+    }
+
+    let mut decoded = Vec::new();
+
+    FlateGzipDecoder::new(body).read_to_end(&mut decoded)?;
+
+    Ok(Cow::Owned(decoded))
+}
+
+
+/// Map the reply of the 2.x write API onto the outcome of the write
+fn write_reply(status: u16, headers: &ReqwHeaderMap, body: &[u8]) -> InfluxResult<()>
+{
+    match status
+    {
+        204 => { Ok(()) }
+
+        400 => { Err(InfluxError::WriteMalformed(json::from_slice::<ApiMalformationError>(body)?)) }
+        401 => { Err(InfluxError::WriteUnauthorized(json::from_slice::<ApiGenericError>(body)?)) }
+        403 => { Err(InfluxError::WriteUnauthenticated(json::from_slice::<ApiGenericError>(body)?)) }
+        413 => { Err(InfluxError::WriteOversized(json::from_slice::<ApiOversizeError>(body)?)) }
+        429 => { Err(InfluxError::WriteOverquota(delay_error(headers, body))) }
+        503 => { Err(InfluxError::WriteUnready(delay_error(headers, body))) }
+
+        _   => { Err(InfluxError::WriteUnknown(json::from_slice::<ApiGenericError>(body)?)) }
+    }
+}
+
+
+/// The delay may come in the reply, in the `Retry-After` header or not at all. Proxies and load balancers in front of
+/// the server answer with plain text or HTML rather than JSON, which is taken as a reply without delay.
+pub(crate) fn delay_error(headers: &ReqwHeaderMap, body: &[u8]) -> ApiDelayError
+{
+    let mut error = json::from_slice::<ApiDelayError>(body)
+        .unwrap_or_default();
+
+    if let Some(delay) = retry_after(headers) {
+        error.retry_after(delay);
+    }
+
+    error
+}
+
+
+/// Seconds to wait as per the `Retry-After` header, given either in seconds or as HTTP date
+fn retry_after(headers: &ReqwHeaderMap) -> Option<i64>
+{
+    let value = headers.get("Retry-After")?
+        .to_str().ok()?
+        .trim();
+
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Some(seconds);
+    }
+
+    let until = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+
+    Some((until.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_seconds().max(0))
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use crate::ApiDelayError;
+    use crate::ApiOversizeError;
+
+    use crate::FlateLevel;
+    use crate::FlateGzipBuilder;
+
+    use std::io::Write;
+    use std::time::Duration;
+
+
+    fn record(lines: usize) -> Record
+    {
+        let mut rec = Record::new("org", "bucket");
+
+        for idx in 0..lines {
+            rec.measurement("cpu").field("idx", idx as i64);
+        }
+
+        rec
+    }
+
+    fn unready(range: Range<usize>) -> (Record, ChunkError)
+    {
+        (record(range.len()), ChunkError::new(range, InfluxError::WriteUnready(ApiDelayError::default())))
+    }
+
+    fn oversized(range: Range<usize>) -> (Record, ChunkError)
+    {
+        (record(range.len()), ChunkError::new(range, InfluxError::WriteOversized(ApiOversizeError::new("too large".into()))))
+    }
+
+    fn retry_delay(error: &InfluxError) -> Option<Duration>
+    {
+        RetryPolicy::new(3).backoff(Duration::ZERO, Duration::ZERO).next_delay(1, error, Duration::ZERO)
+    }
+
+
+    #[test]
+    fn decodes_gzipped_error_reply()
+    {
+        let reply = br#"{"code":"invalid","err":"bad timestamp","line":2,"message":"unable to parse","op":"write"}"#;
+
+        let mut gzipenc = FlateGzipBuilder::new().write(Vec::new(), FlateLevel::default());
+        gzipenc.write_all(reply).unwrap();
+
+        let body = gzipenc.finish().unwrap();
+
+        let mut headers = ReqwHeaderMap::new();
+        headers.insert("Content-Encoding", "gzip".parse().unwrap());
+
+        assert_eq!(decode_body(&headers, &body).unwrap().as_ref(), &reply[..]);
+        assert_eq!(decode_body(&ReqwHeaderMap::new(), reply).unwrap().as_ref(), &reply[..]);
+
+        match WriteOptions::default().write_reply(400, &headers, &body)
+        {
+            Err(InfluxError::WriteMalformed(e)) => { assert!(e.to_string().contains("unable to parse"), "{}", e); }
+            other                               => { panic!("Unexpected reply: {:?}", other); }
+        }
+    }
+
+
+    #[test]
+    fn takes_plain_text_delay_reply_as_retryable()
+    {
+        let mut headers = ReqwHeaderMap::new();
+
+        headers.insert("Content-Type", "text/plain".parse().unwrap());
+        headers.insert("Retry-After", "5".parse().unwrap());
+
+        match WriteOptions::default().write_reply(503, &headers, b"Service Unavailable\n")
+        {
+            Err(e @ InfluxError::WriteUnready(_)) => { assert_eq!(retry_delay(&e), Some(Duration::from_secs(5))); }
+            other                                 => { panic!("Unexpected reply: {:?}", other); }
+        }
+
+        headers.remove("Retry-After");
+
+        match WriteOptions::default().write_reply(429, &headers, b"<html>Too Many Requests</html>")
+        {
+            Err(InfluxError::WriteOverquota(e)) => { assert_eq!(e.delay().as_secs(), 0); }
+            other                               => { panic!("Unexpected reply: {:?}", other); }
+        }
+    }
+
+
+    #[test]
+    fn replay_written_record_leaves_backlog()
+    {
+        let outcome = replay(&record(4), Vec::new());
+
+        assert!(outcome.rewrite);
+        assert!(outcome.keep.is_empty());
+        assert!(outcome.result.is_ok());
+    }
+
+    #[test]
+    fn replay_unwritten_record_stays_untouched()
+    {
+        let outcome = replay(&record(4), vec![unready(0..4)]);
+
+        assert!(! outcome.rewrite);
+        assert_eq!(outcome.keep.len(), 1);
+        assert!(matches!(outcome.result, Err(InfluxError::Annotated(_, ref e)) if matches!(**e, InfluxError::WriteUnready(_))));
+    }
+
+    #[test]
+    fn replay_keeps_only_failed_chunks()
+    {
+        let outcome = replay(&record(4), vec![unready(1..2), unready(3..4)]);
+
+        assert!(outcome.rewrite);
+        assert_eq!(outcome.keep.iter().map(|chunk| chunk.measurements.len()).sum::<usize>(), 2);
+        assert!(matches!(outcome.result, Err(InfluxError::Annotated(_, ref e)) if matches!(**e, InfluxError::WritePartial(_))));
+    }
+
+    #[test]
+    fn replay_drops_lines_that_never_fit()
+    {
+        let outcome = replay(&record(1), vec![oversized(0..1)]);
+
+        assert!(outcome.rewrite);
+        assert!(outcome.keep.is_empty());
+        assert!(matches!(outcome.result, Err(InfluxError::Annotated(_, ref e)) if matches!(**e, InfluxError::WriteOversized(_))));
+
+        let mixed = replay(&record(3), vec![oversized(0..1), unready(1..3)]);
+
+        assert!(mixed.rewrite);
+        assert_eq!(mixed.keep.len(), 1);
+        assert!(mixed.result.is_err());
+    }
+}